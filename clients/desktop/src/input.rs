@@ -0,0 +1,460 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use ironboy_core::{InputMacro, JoypadButton, TiltDirection, gb::GameBoy};
+use sdl2::{
+    GameControllerSubsystem, Sdl,
+    controller::{Axis, Button, GameController},
+    event::Event,
+    keyboard::Keycode,
+};
+use serde::{Deserialize, Serialize};
+
+// How far a stick has to be pushed before it counts as a d-pad press, out of 32767
+const DEFAULT_STICK_DEADZONE: i16 = 16000;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Action {
+    Joypad(JoypadButton),
+    Tilt(TiltDirection),
+    Turbo(JoypadButton),
+    RecordMacro,
+    PlayMacro,
+    // Macro loaded from a file, by its position in the `[macros]` table
+    Macro(usize),
+    VolumeDown,
+    VolumeUp,
+    CyclePalette,
+    CycleColorCorrection,
+    ToggleFrameBlending,
+    CycleFilter,
+    ToggleScaling,
+    ToggleFullscreen,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::Joypad(JoypadButton::A),
+        Action::Joypad(JoypadButton::B),
+        Action::Joypad(JoypadButton::Select),
+        Action::Joypad(JoypadButton::Start),
+        Action::Joypad(JoypadButton::Up),
+        Action::Joypad(JoypadButton::Down),
+        Action::Joypad(JoypadButton::Left),
+        Action::Joypad(JoypadButton::Right),
+        Action::Tilt(TiltDirection::Up),
+        Action::Tilt(TiltDirection::Down),
+        Action::Tilt(TiltDirection::Left),
+        Action::Tilt(TiltDirection::Right),
+        Action::Turbo(JoypadButton::A),
+        Action::Turbo(JoypadButton::B),
+        Action::RecordMacro,
+        Action::PlayMacro,
+        Action::VolumeDown,
+        Action::VolumeUp,
+        Action::CyclePalette,
+        Action::CycleColorCorrection,
+        Action::ToggleFrameBlending,
+        Action::CycleFilter,
+        Action::ToggleScaling,
+        Action::ToggleFullscreen,
+        Action::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Joypad(JoypadButton::A) => "a",
+            Action::Joypad(JoypadButton::B) => "b",
+            Action::Joypad(JoypadButton::Select) => "select",
+            Action::Joypad(JoypadButton::Start) => "start",
+            Action::Joypad(JoypadButton::Up) => "up",
+            Action::Joypad(JoypadButton::Down) => "down",
+            Action::Joypad(JoypadButton::Left) => "left",
+            Action::Joypad(JoypadButton::Right) => "right",
+            Action::Tilt(TiltDirection::Up) => "tilt_up",
+            Action::Tilt(TiltDirection::Down) => "tilt_down",
+            Action::Tilt(TiltDirection::Left) => "tilt_left",
+            Action::Tilt(TiltDirection::Right) => "tilt_right",
+            Action::Turbo(JoypadButton::A) => "turbo_a",
+            Action::Turbo(JoypadButton::B) => "turbo_b",
+            Action::Turbo(_) => "turbo",
+            Action::RecordMacro => "record_macro",
+            Action::PlayMacro => "play_macro",
+            Action::Macro(_) => "macro",
+            Action::VolumeDown => "volume_down",
+            Action::VolumeUp => "volume_up",
+            Action::CyclePalette => "cycle_palette",
+            Action::CycleColorCorrection => "cycle_color_correction",
+            Action::ToggleFrameBlending => "toggle_frame_blending",
+            Action::CycleFilter => "cycle_filter",
+            Action::ToggleScaling => "toggle_scaling",
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+// One key or button name, or a list of them
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Names {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Names {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Names::One(name) => vec![name],
+            Names::Many(names) => names,
+        }
+    }
+
+    fn from_vec(mut names: Vec<String>) -> Self {
+        match names.len() {
+            1 => Names::One(names.remove(0)),
+            _ => Names::Many(names),
+        }
+    }
+}
+
+// Layout of the bindings file, actions map to SDL key names and SDL game controller button names.
+// Macros map a key name to a macro file.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct BindingsFile {
+    stick_deadzone: Option<i16>,
+    keyboard: BTreeMap<String, Names>,
+    controller: BTreeMap<String, Names>,
+    macros: BTreeMap<String, String>,
+}
+
+pub struct Bindings {
+    keyboard: HashMap<Keycode, Action>,
+    controller: HashMap<Button, Action>,
+    macro_files: BTreeMap<String, String>,
+    macros: Vec<InputMacro>,
+    pub stick_deadzone: i16,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let keyboard = [
+            (Keycode::X, Action::Joypad(JoypadButton::A)),
+            (Keycode::Z, Action::Joypad(JoypadButton::B)),
+            (Keycode::Return, Action::Joypad(JoypadButton::Select)),
+            (Keycode::Space, Action::Joypad(JoypadButton::Start)),
+            (Keycode::Up, Action::Joypad(JoypadButton::Up)),
+            (Keycode::Down, Action::Joypad(JoypadButton::Down)),
+            (Keycode::Left, Action::Joypad(JoypadButton::Left)),
+            (Keycode::Right, Action::Joypad(JoypadButton::Right)),
+            (Keycode::I, Action::Tilt(TiltDirection::Up)),
+            (Keycode::K, Action::Tilt(TiltDirection::Down)),
+            (Keycode::J, Action::Tilt(TiltDirection::Left)),
+            (Keycode::L, Action::Tilt(TiltDirection::Right)),
+            (Keycode::W, Action::Turbo(JoypadButton::A)),
+            (Keycode::Q, Action::Turbo(JoypadButton::B)),
+            (Keycode::R, Action::RecordMacro),
+            (Keycode::M, Action::PlayMacro),
+            (Keycode::Num1, Action::VolumeDown),
+            (Keycode::Num2, Action::VolumeUp),
+            (Keycode::P, Action::CyclePalette),
+            (Keycode::C, Action::CycleColorCorrection),
+            (Keycode::B, Action::ToggleFrameBlending),
+            (Keycode::F, Action::CycleFilter),
+            (Keycode::S, Action::ToggleScaling),
+            (Keycode::F11, Action::ToggleFullscreen),
+            (Keycode::Escape, Action::Quit),
+        ];
+        // SDL names buttons by their position on an Xbox pad, the Game Boy's A is the right one of the pair
+        let controller = [
+            (Button::B, Action::Joypad(JoypadButton::A)),
+            (Button::A, Action::Joypad(JoypadButton::B)),
+            (Button::Back, Action::Joypad(JoypadButton::Select)),
+            (Button::Start, Action::Joypad(JoypadButton::Start)),
+            (Button::DPadUp, Action::Joypad(JoypadButton::Up)),
+            (Button::DPadDown, Action::Joypad(JoypadButton::Down)),
+            (Button::DPadLeft, Action::Joypad(JoypadButton::Left)),
+            (Button::DPadRight, Action::Joypad(JoypadButton::Right)),
+            (Button::Y, Action::Turbo(JoypadButton::A)),
+            (Button::X, Action::Turbo(JoypadButton::B)),
+        ];
+        Bindings {
+            keyboard: keyboard.into_iter().collect(),
+            controller: controller.into_iter().collect(),
+            macro_files: BTreeMap::new(),
+            macros: Vec::new(),
+            stick_deadzone: DEFAULT_STICK_DEADZONE,
+        }
+    }
+}
+
+impl Bindings {
+    // Actions listed in the file replace their default keys and buttons, the others keep them
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: BindingsFile = toml::from_str(text).map_err(|error| error.to_string())?;
+        let mut bindings = Bindings::default();
+        if let Some(deadzone) = file.stick_deadzone {
+            bindings.stick_deadzone = deadzone;
+        }
+        for (name, keys) in file.keyboard {
+            let action = Action::from_name(&name).ok_or_else(|| format!("unknown action {name}"))?;
+            bindings.keyboard.retain(|_, bound| *bound != action);
+            for key in keys.into_vec() {
+                let keycode = Keycode::from_name(&key).ok_or_else(|| format!("unknown key {key}"))?;
+                bindings.keyboard.insert(keycode, action);
+            }
+        }
+        for (name, buttons) in file.controller {
+            let action = Action::from_name(&name).ok_or_else(|| format!("unknown action {name}"))?;
+            bindings.controller.retain(|_, bound| *bound != action);
+            for button in buttons.into_vec() {
+                let button = Button::from_string(&button).ok_or_else(|| format!("unknown controller button {button}"))?;
+                bindings.controller.insert(button, action);
+            }
+        }
+        for (key, path) in file.macros {
+            let keycode = Keycode::from_name(&key).ok_or_else(|| format!("unknown key {key}"))?;
+            let input_macro = InputMacro::from_file(Path::new(&path)).map_err(|error| error.to_string())?;
+            bindings.keyboard.insert(keycode, Action::Macro(bindings.macros.len()));
+            bindings.macros.push(input_macro);
+            bindings.macro_files.insert(key, path);
+        }
+        Ok(bindings)
+    }
+
+    // Defaults when the file doesn't exist
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Bindings::parse(&text),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Bindings::default()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut file = BindingsFile {
+            stick_deadzone: Some(self.stick_deadzone),
+            macros: self.macro_files.clone(),
+            ..Default::default()
+        };
+        for action in Action::ALL {
+            let mut keys: Vec<String> = self
+                .keyboard
+                .iter()
+                .filter(|(_, bound)| **bound == action)
+                .map(|(key, _)| key.name())
+                .collect();
+            keys.sort();
+            file.keyboard.insert(action.name().to_string(), Names::from_vec(keys));
+            let mut buttons: Vec<String> = self
+                .controller
+                .iter()
+                .filter(|(_, bound)| **bound == action)
+                .map(|(button, _)| button.string())
+                .collect();
+            buttons.sort();
+            file.controller.insert(action.name().to_string(), Names::from_vec(buttons));
+        }
+        let text = toml::to_string(&file).map_err(|error| error.to_string())?;
+        std::fs::write(path, text).map_err(|error| error.to_string())
+    }
+
+    pub fn key(&self, keycode: Keycode) -> Option<Action> {
+        self.keyboard.get(&keycode).copied()
+    }
+
+    pub fn button(&self, button: Button) -> Option<Action> {
+        self.controller.get(&button).copied()
+    }
+
+    pub fn input_macro(&self, index: usize) -> Option<&InputMacro> {
+        self.macros.get(index)
+    }
+
+    // A key or button only triggers one action, rebinding takes it from its previous one
+    pub fn bind_key(&mut self, action: Action, keycode: Keycode) {
+        self.keyboard.retain(|_, bound| *bound != action);
+        self.keyboard.insert(keycode, action);
+    }
+
+    pub fn bind_button(&mut self, action: Action, button: Button) {
+        self.controller.retain(|_, bound| *bound != action);
+        self.controller.insert(button, action);
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum InputSource {
+    // Keys and controller buttons bound to the joypad
+    Binding,
+    Stick,
+}
+
+// Joypad buttons held through bindings and through the left stick, tracked apart so letting go of one
+// doesn't release a button the other still holds
+#[derive(Default)]
+pub struct HeldButtons {
+    bound: HashSet<JoypadButton>,
+    stick: HashSet<JoypadButton>,
+}
+
+impl HeldButtons {
+    pub fn set(&mut self, game_boy: &mut GameBoy, source: InputSource, button: JoypadButton, pressed: bool) {
+        let was_held = self.is_held(button);
+        let held = match source {
+            InputSource::Binding => &mut self.bound,
+            InputSource::Stick => &mut self.stick,
+        };
+        match pressed {
+            true => held.insert(button),
+            false => held.remove(&button),
+        };
+        match (was_held, self.is_held(button)) {
+            (false, true) => game_boy.button_down(button),
+            (true, false) => game_boy.button_up(button),
+            _ => {}
+        }
+    }
+
+    fn is_held(&self, button: JoypadButton) -> bool {
+        self.bound.contains(&button) || self.stick.contains(&button)
+    }
+}
+
+// Every connected game controller, opened and closed as they are plugged in and out
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    controllers: HashMap<u32, GameController>,
+    // D-pad directions currently held by the left stick, per axis
+    stick_x: Option<JoypadButton>,
+    stick_y: Option<JoypadButton>,
+}
+
+impl Controllers {
+    pub fn new(sdl_context: &Sdl) -> Self {
+        let mut controllers = Controllers {
+            subsystem: sdl_context.game_controller().ok(),
+            controllers: HashMap::new(),
+            stick_x: None,
+            stick_y: None,
+        };
+        let count = controllers
+            .subsystem
+            .as_ref()
+            .and_then(|subsystem| subsystem.num_joysticks().ok())
+            .unwrap_or(0);
+        for index in 0..count {
+            controllers.open(index);
+        }
+        controllers
+    }
+
+    // `index` is the joystick index of ControllerDeviceAdded
+    pub fn open(&mut self, index: u32) {
+        let Some(subsystem) = &self.subsystem else { return };
+        if !subsystem.is_game_controller(index) {
+            return;
+        }
+        match subsystem.open(index) {
+            Ok(controller) => {
+                println!("Controller connected: {}", controller.name());
+                self.controllers.insert(controller.instance_id(), controller);
+            }
+            Err(error) => eprintln!("Unable to open controller: {error}"),
+        }
+    }
+
+    // `instance_id` is the id of ControllerDeviceRemoved
+    pub fn close(&mut self, instance_id: u32) {
+        if let Some(controller) = self.controllers.remove(&instance_id) {
+            println!("Controller disconnected: {}", controller.name());
+        }
+    }
+
+    // D-pad presses and releases caused by moving the left stick
+    pub fn stick_motion(&mut self, axis: Axis, value: i16, deadzone: i16) -> Vec<(JoypadButton, bool)> {
+        let (held, negative, positive) = match axis {
+            Axis::LeftX => (&mut self.stick_x, JoypadButton::Left, JoypadButton::Right),
+            Axis::LeftY => (&mut self.stick_y, JoypadButton::Up, JoypadButton::Down),
+            _ => return Vec::new(),
+        };
+        // A deadzone of 0 would hold a direction with the stick centered, and -i16::MIN overflows
+        let deadzone = deadzone.clamp(1, i16::MAX);
+        let direction = match value {
+            value if value <= -deadzone => Some(negative),
+            value if value >= deadzone => Some(positive),
+            _ => None,
+        };
+        if direction == *held {
+            return Vec::new();
+        }
+
+        let mut changes = Vec::new();
+        if let Some(button) = held.take() {
+            changes.push((button, false));
+        }
+        if let Some(button) = direction {
+            changes.push((button, true));
+        }
+        *held = direction;
+        changes
+    }
+}
+
+// Asks for a new key or controller button for every joypad button and saves them to `path`
+pub fn rebind(sdl_context: &Sdl, path: &Path) -> Result<(), String> {
+    let mut bindings = Bindings::load(path)?;
+    let video_subsystem = sdl_context.video()?;
+    // Keyboard events need a focused window
+    let _window = video_subsystem
+        .window("Iron Boy - rebinding", 480, 120)
+        .position_centered()
+        .build()
+        .map_err(|error| error.to_string())?;
+    let mut controllers = Controllers::new(sdl_context);
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let joypad = Action::ALL.into_iter().filter(|action| matches!(action, Action::Joypad(_)));
+    for action in joypad {
+        println!(
+            "Press a key or controller button for {} (Escape keeps the current binding)",
+            action.name()
+        );
+        loop {
+            match event_pump.wait_event() {
+                Event::Quit { .. } => return Err("rebinding cancelled".to_string()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    bindings.bind_key(action, keycode);
+                    break;
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    bindings.bind_button(action, button);
+                    break;
+                }
+                Event::ControllerDeviceAdded { which, .. } => controllers.open(which),
+                Event::ControllerDeviceRemoved { which, .. } => controllers.close(which),
+                _ => {}
+            }
+        }
+    }
+
+    bindings.save(path)?;
+    println!("Bindings saved to {}", path.display());
+    Ok(())
+}
@@ -1,83 +1,366 @@
-use ironboy_core::{AUDIO_BUFFER_THRESHOLD, FPS, JoypadButton, gb::GameBoy};
-use sdl2::{event::Event, keyboard::Keycode};
+use input::{Action, Bindings, Controllers, HeldButtons, InputSource};
+use ironboy_core::{
+    AUDIO_BUFFER_THRESHOLD, ColorCorrection, DmgPalettePreset, FPS, Filter, FrameBuffer, GameBoyEvent, HighPass, InputMacro, LoadMode,
+    Model, Movie, PaletteCombo, SAMPLING_FREQUENCY, SocdPolicy,
+    gb::{GameBoy, GameBoyOptions},
+    rom,
+};
+use rumble::Rumble;
+use sdl2::event::{Event, WindowEvent};
 use std::{
     collections::VecDeque,
     env,
-    fs::File,
-    io::Read,
+    io::{self, BufRead, Write},
+    path::Path,
     sync::{Arc, Mutex},
 };
+use video::{Scaling, Screen};
 
 pub mod audio;
+pub mod input;
+pub mod rumble;
 pub mod video;
 
 const FRAME_DURATION_NANOS: f32 = 1_000_000_000.0 / FPS;
 const FRAME_DURATION: std::time::Duration = std::time::Duration::from_nanos(FRAME_DURATION_NANOS as u64);
+// Read from the working directory when no other file is given
+const DEFAULT_BINDINGS: &str = "bindings.toml";
+// Where the last recorded macro is kept, replayed by the play macro key
+const RECORDED_MACRO: &str = "recorded.macro";
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        panic!("Please provide a file path as an argument");
+    let usage = "Usage: desktop <rom> [--camera <image>] [--model <dmg|mgb|sgb|sgb2|cgb|agb>] [--sgb] [--palette <combo>] \
+                 [--dmg-palette <gray|green|pocket|light|file>] [--color-correction <raw|gbc|accurate|gba>] \
+                 [--frame-blending] [--lcd-persistence <0.0-1.0>] [--scaling <integer|aspect>] [--fullscreen] \
+                 [--filter <nearest|scale2x|scale3x|xbr|lcd|crt>] [--screenshot <png> [--frames <count>]] [--bindings <toml>] \
+                 [--turbo-rate <presses per second>] [--socd <allow|last-wins|neutral>] \
+                 [--sample-rate <hz>] [--high-pass <off|dmg|cgb>] \
+                 [--record-movie <file> | --play-movie <file|bk2|vbm> [--checkpoints <file>]]\n       \
+                 desktop --rebind [<toml>]";
+    let Some(rom_path) = args.get(1) else {
+        panic!("{usage}");
+    };
+    if rom_path == "--rebind" {
+        let path = args.get(2).map_or(DEFAULT_BINDINGS, String::as_str);
+        input::rebind(&sdl2::init().unwrap(), Path::new(path)).expect("Unable to rebind");
+        return;
+    }
+    let mut camera_path = None;
+    let mut model = None;
+    let mut palette_combo = None;
+    let mut dmg_palette = None;
+    let mut color_correction = None;
+    let mut frame_blending = false;
+    let mut lcd_persistence = None;
+    let mut scaling = Scaling::Integer;
+    let mut fullscreen = false;
+    let mut filter = Filter::Nearest;
+    let mut screenshot = None;
+    let mut frames = 60;
+    let mut bindings_path = DEFAULT_BINDINGS;
+    let mut turbo_rate = None;
+    let mut socd = SocdPolicy::default();
+    let mut sample_rate = SAMPLING_FREQUENCY;
+    let mut high_pass = None;
+    let mut record_movie = None;
+    let mut play_movie = None;
+    let mut checkpoints = None;
+    let mut flags = args.iter().skip(2);
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--camera" => camera_path = Some(flags.next().expect(usage)),
+            "--model" => model = Some(flags.next().expect(usage).parse::<Model>().expect(usage)),
+            "--sgb" => model = Some(Model::Sgb),
+            "--color-correction" => color_correction = Some(flags.next().expect(usage).parse::<ColorCorrection>().expect(usage)),
+            "--frame-blending" => frame_blending = true,
+            "--lcd-persistence" => lcd_persistence = Some(flags.next().expect(usage).parse::<f32>().expect(usage)),
+            "--scaling" => scaling = flags.next().expect(usage).parse::<Scaling>().expect(usage),
+            "--fullscreen" => fullscreen = true,
+            "--filter" => filter = flags.next().expect(usage).parse::<Filter>().expect(usage),
+            "--screenshot" => screenshot = Some(flags.next().expect(usage)),
+            "--frames" => frames = flags.next().expect(usage).parse::<u32>().expect(usage),
+            "--bindings" => bindings_path = flags.next().expect(usage),
+            "--record-movie" => record_movie = Some(flags.next().expect(usage)),
+            "--play-movie" => play_movie = Some(flags.next().expect(usage)),
+            "--checkpoints" => checkpoints = Some(flags.next().expect(usage)),
+            "--socd" => socd = flags.next().expect(usage).parse::<SocdPolicy>().expect(usage),
+            "--sample-rate" => sample_rate = flags.next().expect(usage).parse::<u32>().expect(usage),
+            "--high-pass" => high_pass = Some(flags.next().expect(usage).parse::<HighPass>().expect(usage)),
+            "--turbo-rate" => turbo_rate = Some(flags.next().expect(usage).parse::<u32>().expect(usage)),
+            "--dmg-palette" => dmg_palette = Some(flags.next().expect(usage)),
+            "--palette" => palette_combo = Some(flags.next().expect(usage).parse::<PaletteCombo>().expect(usage)),
+            _ => panic!("{usage}"),
+        }
+    }
+
+    let bindings = Bindings::load(Path::new(bindings_path)).expect("Unable to load bindings");
+    let rom = rom::load_rom(Path::new(rom_path), choose_rom).expect("Unable to load ROM");
+    if let Some(patch) = &rom.patch {
+        println!("Applied patch {}", patch.display());
     }
 
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
-    let mut buffer = Vec::new();
-    rom.read_to_end(&mut buffer).expect("Issue while reading file");
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        model,
+        palette_combo,
+    };
+    let mut game_boy = GameBoy::with_options(&rom.name(), rom.data, options).expect("Unable to start ROM");
+    for warning in game_boy.rom_info().warnings() {
+        eprintln!("Warning: {warning}");
+    }
+    // Color models show DMG games through the CGB compatibility palettes and the SGB through its own
+    let shows_dmg_palette = matches!(game_boy.model(), Model::Dmg | Model::Mgb);
+    if palette_combo.is_some() && !game_boy.model().is_color() {
+        eprintln!("Warning: boot combo palettes are only used by the cgb and agb models");
+    }
+    let mut dmg_preset = DmgPalettePreset::Gray;
+    if let Some(palette) = dmg_palette {
+        if !shows_dmg_palette {
+            eprintln!("Warning: the DMG palette is only shown on the dmg and mgb models");
+        }
+        match palette.parse::<DmgPalettePreset>() {
+            Ok(preset) => {
+                dmg_preset = preset;
+                game_boy.set_dmg_palette(preset.palette());
+            }
+            Err(_) => game_boy.load_dmg_palette(Path::new(palette)).expect("Unable to load palette"),
+        }
+    }
+    if let Some(correction) = color_correction {
+        game_boy.set_color_correction(correction);
+    }
+    game_boy.set_frame_blending(frame_blending);
+    if let Some(persistence) = lcd_persistence {
+        game_boy.set_lcd_persistence(persistence);
+    }
+    game_boy.set_socd_policy(socd);
+    game_boy.set_sample_rate(sample_rate);
+    if let Some(high_pass) = high_pass {
+        game_boy.set_high_pass(high_pass);
+    }
+    if let Some(rate) = turbo_rate {
+        game_boy.set_turbo_rate(rate);
+    }
+    let mut recorded_macro = InputMacro::from_file(Path::new(RECORDED_MACRO)).ok();
+    // Movies start at power-on, before the first frame runs
+    if let Some(path) = play_movie {
+        let mut movie = game_boy.load_movie(Path::new(path)).expect("Unable to load movie");
+        if let Some(checkpoints) = checkpoints {
+            movie.load_checkpoints(Path::new(checkpoints)).expect("Unable to load checkpoints");
+        }
+        game_boy.play_movie(movie).expect("Unable to play movie");
+    }
+    if record_movie.is_some() {
+        game_boy.start_movie_recording().expect("Unable to record movie");
+    }
+    if let Some(image) = camera_path {
+        game_boy.load_camera_image(Path::new(image)).expect("Unable to load camera image");
+    }
+    // Headless run that only saves the screen after the given number of frames
+    if let Some(path) = screenshot {
+        let mut desynced = false;
+        for _ in 0..frames {
+            game_boy.run();
+            while let Some(event) = game_boy.poll_event() {
+                if let GameBoyEvent::MovieDesync { .. } = event {
+                    report_desync(event);
+                    desynced = true;
+                }
+            }
+        }
+        game_boy.save_screenshot(Path::new(path), filter).expect("Unable to save screenshot");
+        // A failed exit status lets movie replays serve as regression tests
+        if desynced {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let mut game_boy = GameBoy::new(&args[1], buffer);
     let sdl_context = sdl2::init().unwrap();
     let audio_device = audio::create_audio_device(&mut game_boy, &sdl_context);
     audio_device.resume();
-    let mut canvas = video::create_canvas(&sdl_context);
+    let (width, height) = game_boy.screen_size();
+    let mut canvas = video::create_canvas(&sdl_context, width, height);
+    if fullscreen {
+        video::toggle_fullscreen(&mut canvas);
+    }
+    let texture_creator = canvas.texture_creator();
+    let mut screen = Screen::new(&texture_creator, width * filter.scale(), height * filter.scale(), scaling);
+    let mut filtered = FrameBuffer::new(0, 0, false);
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut rumble = Rumble::new(&sdl_context);
+    let mut controllers = Controllers::new(&sdl_context);
+    let mut held_buttons = HeldButtons::default();
 
     'game: loop {
         let frame_start_time = std::time::Instant::now();
-        let frames = game_boy.run();
-        for frame in frames {
-            video::render_screen(&mut canvas, &frame);
+        if game_boy.run() {
+            filter.apply(game_boy.frame(), &mut filtered);
+            screen.render(&mut canvas, &filtered);
         }
+        while let Some(event) = game_boy.poll_event() {
+            match event {
+                GameBoyEvent::Rumble(active) => rumble.set_active(active),
+                GameBoyEvent::CpuLocked { opcode, address } => eprintln!("CPU locked up by illegal opcode {opcode:#04X} at {address:#06X}"),
+                GameBoyEvent::MovieFinished => println!("Movie finished"),
+                GameBoyEvent::MovieDesync { .. } => report_desync(event),
+            }
+        }
+        rumble.apply();
         while should_sync(frame_start_time, &game_boy.cpu.bus.apu.audio_buffer) {
             std::hint::spin_loop();
         }
 
         for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+            let (action, pressed) = match event {
+                Event::Quit { .. } => break 'game,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
                     ..
-                } => break 'game,
-                Event::KeyDown { keycode, .. } => match keycode {
-                    Some(Keycode::X) => game_boy.button_down(JoypadButton::A),
-                    Some(Keycode::Z) => game_boy.button_down(JoypadButton::B),
-                    Some(Keycode::Return) => game_boy.button_down(JoypadButton::Select),
-                    Some(Keycode::Space) => game_boy.button_down(JoypadButton::Start),
-                    Some(Keycode::Up) => game_boy.button_down(JoypadButton::Up),
-                    Some(Keycode::Left) => game_boy.button_down(JoypadButton::Left),
-                    Some(Keycode::Down) => game_boy.button_down(JoypadButton::Down),
-                    Some(Keycode::Right) => game_boy.button_down(JoypadButton::Right),
-                    Some(Keycode::Num1) => game_boy.decrease_volume(),
-                    Some(Keycode::Num2) => game_boy.increase_volume(),
-                    _ => {}
+                } => match bindings.key(keycode) {
+                    Some(action) => (action, true),
+                    None => continue,
                 },
-                Event::KeyUp { keycode, .. } => match keycode {
-                    Some(Keycode::X) => game_boy.button_up(JoypadButton::A),
-                    Some(Keycode::Z) => game_boy.button_up(JoypadButton::B),
-                    Some(Keycode::Return) => game_boy.button_up(JoypadButton::Select),
-                    Some(Keycode::Space) => game_boy.button_up(JoypadButton::Start),
-                    Some(Keycode::Up) => game_boy.button_up(JoypadButton::Up),
-                    Some(Keycode::Left) => game_boy.button_up(JoypadButton::Left),
-                    Some(Keycode::Down) => game_boy.button_up(JoypadButton::Down),
-                    Some(Keycode::Right) => game_boy.button_up(JoypadButton::Right),
-                    _ => {}
+                Event::KeyUp { keycode: Some(keycode), .. } => match bindings.key(keycode) {
+                    Some(action) => (action, false),
+                    None => continue,
                 },
-                _ => {}
+                Event::ControllerButtonDown { button, .. } => match bindings.button(button) {
+                    Some(action) => (action, true),
+                    None => continue,
+                },
+                Event::ControllerButtonUp { button, .. } => match bindings.button(button) {
+                    Some(action) => (action, false),
+                    None => continue,
+                },
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    for (button, pressed) in controllers.stick_motion(axis, value, bindings.stick_deadzone) {
+                        held_buttons.set(&mut game_boy, InputSource::Stick, button, pressed);
+                    }
+                    continue;
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    controllers.open(which);
+                    rumble.open_controller();
+                    continue;
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.close(which);
+                    rumble.open_controller();
+                    continue;
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    screen.present(&mut canvas);
+                    continue;
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    let (width, height) = canvas.window().size();
+                    game_boy.set_tilt_from_pointer(x, y, width, height);
+                    continue;
+                }
+                _ => continue,
             };
+
+            match (action, pressed) {
+                (Action::Joypad(button), pressed) => held_buttons.set(&mut game_boy, InputSource::Binding, button, pressed),
+                (Action::Tilt(direction), true) => game_boy.tilt_key_down(direction),
+                (Action::Tilt(direction), false) => game_boy.tilt_key_up(direction),
+                (Action::Turbo(button), true) => game_boy.turbo_down(button),
+                (Action::Turbo(button), false) => game_boy.turbo_up(button),
+                (_, false) => {}
+                (Action::RecordMacro, true) => match game_boy.stop_recording_macro() {
+                    Some(input_macro) => {
+                        match input_macro.save(Path::new(RECORDED_MACRO)) {
+                            Ok(()) => println!("Macro of {} frames saved to {RECORDED_MACRO}", input_macro.frames()),
+                            Err(error) => eprintln!("{error}"),
+                        }
+                        recorded_macro = Some(input_macro);
+                    }
+                    None => {
+                        println!("Recording macro");
+                        game_boy.start_recording_macro();
+                    }
+                },
+                (Action::PlayMacro, true) => match &recorded_macro {
+                    Some(input_macro) => game_boy.play_macro(input_macro.clone()),
+                    None => println!("No macro recorded yet"),
+                },
+                (Action::Macro(index), true) => {
+                    if let Some(input_macro) = bindings.input_macro(index) {
+                        game_boy.play_macro(input_macro.clone());
+                    }
+                }
+                (Action::Quit, true) => break 'game,
+                (Action::VolumeDown, true) => game_boy.decrease_volume(),
+                (Action::VolumeUp, true) => game_boy.increase_volume(),
+                (Action::CycleColorCorrection, true) => {
+                    let correction = game_boy.color_correction().next();
+                    println!("Color correction: {}", correction.name());
+                    game_boy.set_color_correction(correction);
+                }
+                (Action::ToggleFullscreen, true) => video::toggle_fullscreen(&mut canvas),
+                (Action::ToggleScaling, true) => {
+                    screen.scaling = screen.scaling.next();
+                    screen.present(&mut canvas);
+                }
+                (Action::CycleFilter, true) => {
+                    filter = filter.next();
+                    println!("Filter: {}", filter.name());
+                }
+                (Action::ToggleFrameBlending, true) => game_boy.set_frame_blending(!game_boy.frame_blending()),
+                (Action::CyclePalette, true) => {
+                    dmg_preset = dmg_preset.next();
+                    game_boy.set_dmg_palette(dmg_preset.palette());
+                    match shows_dmg_palette {
+                        true => println!("DMG palette: {}", dmg_preset.name()),
+                        false => eprintln!("Warning: the DMG palette is only shown on the dmg and mgb models"),
+                    }
+                }
+            }
         }
     }
+
+    if let Some(path) = record_movie
+        && let Some(movie) = game_boy.stop_movie_recording()
+    {
+        movie.save(Path::new(path)).expect("Unable to save movie");
+        println!("Movie of {} events saved to {path}", movie.events.len());
+    }
+}
+
+fn report_desync(event: GameBoyEvent) {
+    if let GameBoyEvent::MovieDesync {
+        cycle,
+        address,
+        expected,
+        actual,
+    } = event
+    {
+        eprintln!(
+            "Movie desynced at frame {}: {address:#06X} is {actual:#04X}, expected {expected:#04X}",
+            Movie::frame_at(cycle)
+        );
+    }
 }
 
-fn should_sync(frame_start_time: std::time::Instant, audio_buffer: &Arc<Mutex<VecDeque<u8>>>) -> bool {
+fn should_sync(frame_start_time: std::time::Instant, audio_buffer: &Arc<Mutex<VecDeque<f32>>>) -> bool {
     frame_start_time.elapsed().as_micros() < FRAME_DURATION.as_micros() && audio_buffer.lock().unwrap().len() > AUDIO_BUFFER_THRESHOLD
 }
+
+fn choose_rom(names: &[String]) -> Option<usize> {
+    println!("The archive contains several ROMs:");
+    for (index, name) in names.iter().enumerate() {
+        println!("  {}: {}", index + 1, name);
+    }
+    print!("Select a ROM [1-{}]: ", names.len());
+    io::stdout().flush().ok()?;
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).ok()?;
+    line.trim().parse::<usize>().ok()?.checked_sub(1)
+}
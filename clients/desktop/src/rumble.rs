@@ -0,0 +1,50 @@
+use sdl2::{GameControllerSubsystem, Sdl, controller::GameController};
+
+// Long enough to bridge the gap between two frames, refreshed while the motor stays on
+const RUMBLE_DURATION_MS: u32 = 100;
+
+pub struct Rumble {
+    subsystem: Option<GameControllerSubsystem>,
+    controller: Option<GameController>,
+    active: bool,
+    pulsed: bool,
+}
+
+impl Rumble {
+    pub fn new(sdl_context: &Sdl) -> Self {
+        let mut rumble = Rumble {
+            subsystem: sdl_context.game_controller().ok(),
+            controller: None,
+            active: false,
+            pulsed: false,
+        };
+        rumble.open_controller();
+        rumble
+    }
+
+    pub fn open_controller(&mut self) {
+        if self.controller.as_ref().is_some_and(|controller| controller.attached()) {
+            return;
+        }
+        let Some(subsystem) = &self.subsystem else { return };
+        let count = subsystem.num_joysticks().unwrap_or(0);
+        self.controller = (0..count)
+            .filter(|&index| subsystem.is_game_controller(index))
+            .find_map(|index| subsystem.open(index).ok());
+    }
+
+    // Games pulse the motor several times per frame to vary its strength
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        self.pulsed |= active;
+    }
+
+    // Called once per frame so a motor that stays on keeps rumbling
+    pub fn apply(&mut self) {
+        let on = self.active || self.pulsed;
+        self.pulsed = false;
+        let Some(controller) = &mut self.controller else { return };
+        let strength = if on { 0xFFFF } else { 0 };
+        let _ = controller.set_rumble(strength, strength, RUMBLE_DURATION_MS);
+    }
+}
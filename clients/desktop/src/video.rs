@@ -1,14 +1,54 @@
-use ironboy_core::{VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+use std::str::FromStr;
 
+use ironboy_core::{FrameBuffer, VIEWPORT_WIDTH};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture, TextureCreator},
+    video::{FullscreenType, Window, WindowContext},
+};
+
+// Scale of the plain 160x144 screen, larger SGB frames keep the same window width
 const SCALE: u32 = 6;
-const WINDOW_WIDTH: u32 = (VIEWPORT_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (VIEWPORT_HEIGHT as u32) * SCALE;
 
-pub fn create_canvas(sdl: &sdl2::Sdl) -> Canvas<Window> {
+fn scale(width: usize) -> u32 {
+    (VIEWPORT_WIDTH as u32 * SCALE / width as u32).max(1)
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Scaling {
+    // Largest whole multiple of the frame that fits, every pixel the same size
+    Integer,
+    // Fills the window as far as the frame's aspect ratio allows
+    Aspect,
+}
+
+impl Scaling {
+    pub fn next(&self) -> Scaling {
+        match self {
+            Scaling::Integer => Scaling::Aspect,
+            Scaling::Aspect => Scaling::Integer,
+        }
+    }
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "integer" => Ok(Scaling::Integer),
+            "aspect" => Ok(Scaling::Aspect),
+            _ => Err(format!("unknown scaling {s}")),
+        }
+    }
+}
+
+pub fn create_canvas(sdl: &sdl2::Sdl, width: usize, height: usize) -> Canvas<Window> {
     let video_subsystem = sdl.video().unwrap();
+    let scale = scale(width);
     let window = video_subsystem
-        .window("Iron Boy", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .window("Iron Boy", width as u32 * scale, height as u32 * scale)
         .position_centered()
         .resizable()
         .opengl()
@@ -17,24 +57,75 @@ pub fn create_canvas(sdl: &sdl2::Sdl) -> Canvas<Window> {
     window.into_canvas().present_vsync().accelerated().build().unwrap()
 }
 
-pub fn render_screen(canvas: &mut Canvas<Window>, data: &[(u8, u8, u8)]) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-
-    for x in 0..VIEWPORT_WIDTH {
-        for y in 0..VIEWPORT_HEIGHT {
-            let i = y * VIEWPORT_WIDTH + x;
-            let color = data[i as usize];
-            canvas.set_draw_color(Color::RGB(color.0, color.1, color.2));
-            let rect = Rect::new(
-                (x as u32 * SCALE) as i32,
-                (y as u32 * SCALE) as i32,
-                SCALE + 4, // change this if you want line speration
-                SCALE + 4, // change this if you want line speration
-            );
-            canvas.fill_rect(rect).unwrap();
+// Uploads every frame into one streaming texture that the GPU scales to the window
+pub struct Screen<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    width: usize,
+    height: usize,
+    pub scaling: Scaling,
+}
+
+impl<'a> Screen<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>, width: usize, height: usize, scaling: Scaling) -> Self {
+        Screen {
+            texture_creator,
+            texture: create_texture(texture_creator, width, height),
+            width,
+            height,
+            scaling,
         }
     }
 
-    canvas.present();
+    pub fn render(&mut self, canvas: &mut Canvas<Window>, frame: &FrameBuffer) {
+        if (frame.width(), frame.height()) != (self.width, self.height) {
+            self.texture = create_texture(self.texture_creator, frame.width(), frame.height());
+            (self.width, self.height) = (frame.width(), frame.height());
+        }
+        self.texture.update(None, frame.as_bytes(), frame.width() * 4).unwrap();
+        self.present(canvas);
+    }
+
+    // Draws the last frame again, e.g. after the window was resized
+    pub fn present(&self, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+        let output = canvas.output_size().unwrap();
+        let destination = destination(output, self.width as u32, self.height as u32, self.scaling);
+        canvas.copy(&self.texture, None, destination).unwrap();
+        canvas.present();
+    }
+}
+
+// XRGB8888 matches the framebuffer's packed pixels
+fn create_texture(texture_creator: &TextureCreator<WindowContext>, width: usize, height: usize) -> Texture<'_> {
+    texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB888, width as u32, height as u32)
+        .unwrap()
+}
+
+// Centered area of the window the frame is drawn to
+fn destination((output_width, output_height): (u32, u32), width: u32, height: u32, scaling: Scaling) -> Rect {
+    let (scaled_width, scaled_height) = match scaling {
+        Scaling::Integer => {
+            let scale = (output_width / width).min(output_height / height).max(1);
+            (width * scale, height * scale)
+        }
+        Scaling::Aspect if output_width * height > output_height * width => (width * output_height / height, output_height),
+        Scaling::Aspect => (output_width, height * output_width / width),
+    };
+    let x = (output_width as i32 - scaled_width as i32) / 2;
+    let y = (output_height as i32 - scaled_height as i32) / 2;
+    Rect::new(x, y, scaled_width.max(1), scaled_height.max(1))
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) {
+    let window = canvas.window_mut();
+    let fullscreen = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    if let Err(error) = window.set_fullscreen(fullscreen) {
+        eprintln!("Unable to toggle fullscreen: {error}");
+    }
 }
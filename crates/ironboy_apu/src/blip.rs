@@ -0,0 +1,79 @@
+use std::f64::consts::PI;
+
+// Sub-sample positions a step is placed at, and the output samples each step is spread over
+const PHASES: usize = 64;
+const TAPS: usize = 16;
+// Share of the output Nyquist frequency let through, the rest of the band is left for the window's roll-off
+const CUTOFF: f64 = 0.9;
+
+// Band-limited step synthesis in the style of blip_buf. Amplitude changes are added at the cycle they
+// happen as windowed-sinc impulses and integrated when read, so square and noise edges that fall
+// between output samples don't alias the way point sampling does.
+pub struct BlipBuffer {
+    kernel: Vec<[f32; TAPS]>,
+    // Output samples per CPU cycle
+    ratio: f64,
+    // Output samples the current frame started at, only its fraction is left after reading
+    offset: f64,
+    deltas: Vec<f32>,
+    integrator: f32,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            kernel: (0..=PHASES).map(kernel_phase).collect(),
+            ratio: sample_rate as f64 / clock_rate as f64,
+            offset: 0.0,
+            deltas: vec![0.0; TAPS],
+            integrator: 0.0,
+        }
+    }
+
+    // Adds an amplitude change `time` cycles into the current frame
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let position = self.offset + time as f64 * self.ratio;
+        let start = position as usize;
+        let phase = ((position - start as f64) * PHASES as f64).round() as usize;
+        if self.deltas.len() < start + TAPS {
+            self.deltas.resize(start + TAPS, 0.0);
+        }
+        for (delta_sample, weight) in self.deltas[start..start + TAPS].iter_mut().zip(self.kernel[phase]) {
+            *delta_sample += delta * weight;
+        }
+    }
+
+    // Ends the current frame after `time` cycles, the samples it completed can then be read
+    pub fn end_frame(&mut self, time: u32) {
+        self.offset += time as f64 * self.ratio;
+    }
+
+    // Appends every completed sample to `samples`
+    pub fn read_samples(&mut self, samples: &mut Vec<f32>) {
+        let count = self.offset as usize;
+        if self.deltas.len() < count + TAPS {
+            self.deltas.resize(count + TAPS, 0.0);
+        }
+        for delta in self.deltas.drain(..count) {
+            self.integrator += delta;
+            samples.push(self.integrator);
+        }
+        self.offset -= count as f64;
+    }
+}
+
+// Impulse of a step starting `phase / PHASES` of a sample late, delayed by half the taps
+fn kernel_phase(phase: usize) -> [f32; TAPS] {
+    let center = (TAPS / 2 - 1) as f64 + phase as f64 / PHASES as f64;
+    let mut impulse = [0.0; TAPS];
+    for (tap, weight) in impulse.iter_mut().enumerate() {
+        let x = tap as f64 - center;
+        let sinc = if x == 0.0 { 1.0 } else { (PI * CUTOFF * x).sin() / (PI * CUTOFF * x) };
+        // Blackman window over the taps
+        let window = 0.42 + 0.5 * (2.0 * PI * x / TAPS as f64).cos() + 0.08 * (4.0 * PI * x / TAPS as f64).cos();
+        *weight = sinc * window;
+    }
+    // Every step must end up at exactly its height once integrated
+    let sum: f64 = impulse.iter().sum();
+    impulse.map(|weight| (weight / sum) as f32)
+}
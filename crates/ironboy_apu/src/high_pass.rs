@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use ironboy_common::{CPU_CLOCK_SPEED, Model};
+
+// The capacitor in series with each output that blocks the DC offset of the channel DACs
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum HighPass {
+    Off,
+    Dmg,
+    // The MGB and the color models charge faster, which cuts more of the bass
+    Cgb,
+}
+
+impl HighPass {
+    pub const ALL: [HighPass; 3] = [HighPass::Off, HighPass::Dmg, HighPass::Cgb];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HighPass::Off => "off",
+            HighPass::Dmg => "dmg",
+            HighPass::Cgb => "cgb",
+        }
+    }
+
+    pub fn for_model(model: Model) -> Self {
+        match model {
+            Model::Dmg | Model::Sgb | Model::Sgb2 => HighPass::Dmg,
+            Model::Mgb | Model::Cgb | Model::Agb => HighPass::Cgb,
+        }
+    }
+
+    // Share of its charge the capacitor keeps over one CPU cycle
+    fn charge_factor(&self) -> Option<f64> {
+        match self {
+            HighPass::Off => None,
+            HighPass::Dmg => Some(0.999958),
+            HighPass::Cgb => Some(0.998943),
+        }
+    }
+}
+
+impl FromStr for HighPass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        HighPass::ALL
+            .into_iter()
+            .find(|high_pass| high_pass.name() == s)
+            .ok_or_else(|| format!("unknown high-pass filter {s}"))
+    }
+}
+
+pub struct HighPassFilter {
+    // Share of its charge the capacitor keeps over one output sample
+    charge: Option<f32>,
+    capacitor: f32,
+}
+
+impl HighPassFilter {
+    pub fn new(high_pass: HighPass, sample_rate: u32) -> Self {
+        Self {
+            charge: high_pass
+                .charge_factor()
+                .map(|factor| factor.powf(CPU_CLOCK_SPEED as f64 / sample_rate as f64) as f32),
+            capacitor: 0.0,
+        }
+    }
+
+    pub fn apply(&mut self, input: f32) -> f32 {
+        let Some(charge) = self.charge else {
+            return input;
+        };
+        let output = input - self.capacitor;
+        self.capacitor = input - output * charge;
+        output
+    }
+}
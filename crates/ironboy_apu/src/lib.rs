@@ -1,5 +1,7 @@
+use blip::BlipBuffer;
 use channel::Channel;
-use ironboy_common::{CPU_CLOCK_SPEED, SystemMemoryAccess};
+use high_pass::HighPassFilter;
+use ironboy_common::{CPU_CLOCK_SPEED, Model, SystemMemoryAccess};
 use frame_sequencer::FrameSequencer;
 use mixer::Mixer;
 use noise::NoiseChannel;
@@ -8,20 +10,27 @@ use wave::WaveChannel;
 
 use std::{
     collections::VecDeque,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
 };
 
+pub use high_pass::HighPass;
+
+mod blip;
 mod channel;
 mod frame_sequencer;
+mod high_pass;
 mod mixer;
 mod noise;
 mod square;
+#[cfg(test)]
+mod tests;
 mod wave;
 
 pub const SAMPLING_RATE: u16 = 1024;
-pub const SAMPLING_FREQUENCY: u16 = 44100;
+pub const SAMPLING_FREQUENCY: u32 = 44100;
 pub const APU_CLOCK_SPEED: u16 = 512;
-const CPU_CYCLES_PER_SAMPLE: f32 = CPU_CLOCK_SPEED as f32 / SAMPLING_FREQUENCY as f32;
+// Cycles between two reads of the band-limited buffers
+const OUTPUT_FRAME_CYCLES: u32 = 4096;
 pub const AUDIO_BUFFER_THRESHOLD: usize = SAMPLING_RATE as usize * 4;
 
 pub struct Apu {
@@ -34,8 +43,16 @@ pub struct Apu {
     pub right_volume: u8,
     pub left_volume: u8,
     enabled: bool,
-    counter: f32,
-    pub audio_buffer: Arc<Mutex<VecDeque<u8>>>,
+    sample_rate: u32,
+    high_pass: HighPass,
+    // Left then right for the pair below
+    blips: [BlipBuffer; 2],
+    filters: [HighPassFilter; 2],
+    last_output: [f32; 2],
+    // Cycles into the current output frame
+    clock: u32,
+    // Interleaved left and right samples between -1 and 1
+    pub audio_buffer: Arc<Mutex<VecDeque<f32>>>,
 }
 
 impl SystemMemoryAccess for Apu {
@@ -81,7 +98,8 @@ impl SystemMemoryAccess for Apu {
 }
 
 impl Apu {
-    pub fn new() -> Self {
+    pub fn new(model: Model) -> Self {
+        let high_pass = HighPass::for_model(model);
         Self {
             ch1: SquareChannel::new(true),
             ch2: SquareChannel::new(false),
@@ -92,32 +110,113 @@ impl Apu {
             right_volume: 0,
             left_volume: 0,
             enabled: false,
-            counter: 0.0,
-            audio_buffer: Arc::new(Mutex::new(VecDeque::from(vec![0; AUDIO_BUFFER_THRESHOLD]))),
+            sample_rate: SAMPLING_FREQUENCY,
+            high_pass,
+            blips: [0; 2].map(|_| BlipBuffer::new(CPU_CLOCK_SPEED, SAMPLING_FREQUENCY)),
+            filters: [0; 2].map(|_| HighPassFilter::new(high_pass, SAMPLING_FREQUENCY)),
+            last_output: [0.0; 2],
+            clock: 0,
+            audio_buffer: Arc::new(Mutex::new(VecDeque::from(vec![0.0; AUDIO_BUFFER_THRESHOLD]))),
         }
     }
 
     pub fn cycle(&mut self, cycles: u32) {
+        if self.enabled {
+            self.frame_sequencer
+                .cycle(cycles, &mut self.ch1, &mut self.ch2, &mut self.ch3, &mut self.ch4);
+            self.ch1.cycle(cycles);
+            self.ch2.cycle(cycles);
+            self.ch3.cycle(cycles);
+            self.ch4.cycle(cycles);
+        }
+        self.clock += cycles;
+
+        // Silence keeps being produced while the APU is off so the frontend's audio clock doesn't stall
+        let output = self.output();
+        for (side, blip) in self.blips.iter_mut().enumerate() {
+            if output[side] != self.last_output[side] {
+                blip.add_delta(self.clock, output[side] - self.last_output[side]);
+            }
+        }
+        self.last_output = output;
+
+        if self.clock >= OUTPUT_FRAME_CYCLES {
+            self.end_output_frame();
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Samples per second and channel written to `audio_buffer`
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.reset_output();
+    }
+
+    pub fn high_pass(&self) -> HighPass {
+        self.high_pass
+    }
+
+    pub fn set_high_pass(&mut self, high_pass: HighPass) {
+        self.high_pass = high_pass;
+        self.reset_output();
+    }
+
+    // Moves up to `samples.len()` interleaved samples out of `audio_buffer`, returning how many were read
+    pub fn read_samples_f32(&mut self, samples: &mut [f32]) -> usize {
+        let mut audio_buffer = self.audio_buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = samples.len().min(audio_buffer.len());
+        for (sample, value) in samples.iter_mut().zip(audio_buffer.drain(..count)) {
+            *sample = value;
+        }
+        count
+    }
+
+    pub fn read_samples_i16(&mut self, samples: &mut [i16]) -> usize {
+        let mut audio_buffer = self.audio_buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = samples.len().min(audio_buffer.len());
+        for (sample, value) in samples.iter_mut().zip(audio_buffer.drain(..count)) {
+            *sample = (value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        }
+        count
+    }
+
+    // Left and right amplitudes after the master volume
+    fn output(&self) -> [f32; 2] {
         if !self.enabled {
-            return;
+            return [0.0; 2];
         }
+        let (left, right) = self
+            .mixer
+            .mix([self.ch1.output(), self.ch2.output(), self.ch3.output(), self.ch4.output()]);
+        [left * self.left_volume as f32 / 8.0, right * self.right_volume as f32 / 8.0]
+    }
 
-        self.frame_sequencer
-            .cycle(cycles, &mut self.ch1, &mut self.ch2, &mut self.ch3, &mut self.ch4);
-        self.ch1.cycle(cycles);
-        self.ch2.cycle(cycles);
-        self.ch3.cycle(cycles);
-        self.ch4.cycle(cycles);
-        self.counter += cycles as f32;
-
-        while self.counter >= CPU_CYCLES_PER_SAMPLE {
-            let (output_left, output_right) = self
-                .mixer
-                .mix([self.ch1.output(), self.ch2.output(), self.ch3.output(), self.ch4.output()]);
-            self.audio_buffer.lock().unwrap().push_back(output_left);
-            self.audio_buffer.lock().unwrap().push_back(output_right);
-            self.counter -= CPU_CYCLES_PER_SAMPLE;
+    fn end_output_frame(&mut self) {
+        let mut sides = [Vec::new(), Vec::new()];
+        for (blip, samples) in self.blips.iter_mut().zip(sides.iter_mut()) {
+            blip.end_frame(self.clock);
+            blip.read_samples(samples);
         }
+        self.clock = 0;
+
+        let [left, right] = sides;
+        let [left_filter, right_filter] = &mut self.filters;
+        // A frontend thread that panicked while holding the lock must not take the emulator down with it
+        let mut audio_buffer = self.audio_buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        for (left, right) in left.into_iter().zip(right) {
+            audio_buffer.push_back(left_filter.apply(left));
+            audio_buffer.push_back(right_filter.apply(right));
+        }
+    }
+
+    fn reset_output(&mut self) {
+        self.blips = [0; 2].map(|_| BlipBuffer::new(CPU_CLOCK_SPEED, self.sample_rate));
+        self.filters = [0; 2].map(|_| HighPassFilter::new(self.high_pass, self.sample_rate));
+        self.last_output = [0.0; 2];
+        self.clock = 0;
     }
 
     fn master_control(&self) -> u8 {
@@ -155,7 +254,5 @@ impl Apu {
         self.mixer.reset();
         self.left_volume = 0;
         self.right_volume = 0;
-        self.counter = 0.0;
-        self.audio_buffer.lock().unwrap().clear();
     }
 }
@@ -7,17 +7,19 @@ impl Mixer {
         Self { panning: [false; 8] }
     }
 
-    pub fn mix(&self, channels_outputs: [u8; 4]) -> (u8, u8) {
-        let (mut output_left, mut output_right) = (0, 0);
-        for (i, output) in channels_outputs.iter().enumerate() {
+    // Left and right amplitudes between 0 and 1 from the 4-bit channel outputs
+    pub fn mix(&self, channels_outputs: [u8; 4]) -> (f32, f32) {
+        let (mut output_left, mut output_right) = (0.0, 0.0);
+        for (i, &output) in channels_outputs.iter().enumerate() {
+            let amplitude = output as f32 / 15.0;
             if self.panning[i + 4] {
-                output_left += output;
+                output_left += amplitude;
             }
             if self.panning[i] {
-                output_right += output;
+                output_right += amplitude;
             }
         }
-        (output_left / 4, output_right / 4)
+        (output_left / 4.0, output_right / 4.0)
     }
 
     pub fn read(&self) -> u8 {
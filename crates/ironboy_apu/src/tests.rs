@@ -0,0 +1,54 @@
+use ironboy_common::CPU_CLOCK_SPEED;
+
+use crate::{
+    blip::BlipBuffer,
+    high_pass::{HighPass, HighPassFilter},
+};
+
+const SAMPLE_RATE: u32 = 48000;
+
+#[test]
+fn blip_buffer_steps_settle_at_their_height() {
+    let mut blip = BlipBuffer::new(CPU_CLOCK_SPEED, SAMPLE_RATE);
+    // Two steps between output samples, the second one falling
+    blip.add_delta(1000, 0.75);
+    blip.add_delta(3001, -0.25);
+    blip.end_frame(70224);
+    let mut samples = Vec::new();
+    blip.read_samples(&mut samples);
+
+    // 70224 cycles last 803.7 samples, the fraction is carried into the next frame
+    assert_eq!(samples.len(), 803);
+    assert!(samples[..4].iter().all(|&sample| sample.abs() < 1e-6));
+    assert!(samples[100..].iter().all(|&sample| (sample - 0.5).abs() < 1e-5));
+
+    blip.end_frame(70224);
+    blip.read_samples(&mut samples);
+    assert_eq!(samples.len(), 1607);
+}
+
+// Output of a filter fed a constant level of 1.0 for `count` samples
+fn filtered_dc(high_pass: HighPass, count: usize) -> Vec<f32> {
+    let mut filter = HighPassFilter::new(high_pass, SAMPLE_RATE);
+    (0..count).map(|_| filter.apply(1.0)).collect()
+}
+
+#[test]
+fn high_pass_filter_decays_dc_at_the_model_rate() {
+    assert!(filtered_dc(HighPass::Off, 100).iter().all(|&sample| sample == 1.0));
+
+    // The capacitor keeps its share of the charge for every CPU cycle of a sample
+    for (high_pass, factor) in [(HighPass::Dmg, 0.999958f64), (HighPass::Cgb, 0.998943)] {
+        let charge = factor.powf(CPU_CLOCK_SPEED as f64 / SAMPLE_RATE as f64);
+        let output = filtered_dc(high_pass, 1000);
+        for (index, &sample) in output.iter().enumerate().step_by(100) {
+            let expected = charge.powi(index as i32);
+            assert!(
+                (sample as f64 - expected).abs() < 1e-3,
+                "{high_pass:?} sample {index} is {sample}, expected {expected}"
+            );
+        }
+    }
+    // The color models cut more of the bass
+    assert!(filtered_dc(HighPass::Cgb, 100)[99] < filtered_dc(HighPass::Dmg, 100)[99]);
+}
@@ -64,9 +64,8 @@ impl Channel for WaveChannel {
         self.length_timer.cycle(&mut self.base.enabled)
     }
 
-    fn volume_envelope_cycle(&mut self) {
-        unimplemented!()
-    }
+    // The wave channel has no volume envelope
+    fn volume_envelope_cycle(&mut self) {}
 
     fn trigger(&mut self) {
         if self.base.dac_enabled {
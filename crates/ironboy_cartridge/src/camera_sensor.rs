@@ -0,0 +1,200 @@
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+
+use super::CartridgeError;
+
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+pub const SENSOR_REGISTERS: usize = 0x36;
+pub const IMAGE_SIZE: usize = (SENSOR_WIDTH / 8) * (SENSOR_HEIGHT / 8) * 16;
+
+const BASE_CAPTURE_CYCLES: u32 = 129792;
+const EDGE_EXTRA_CYCLES: u32 = 2048;
+const DITHER_MATRIX_START: usize = 0x06;
+
+const GAIN_TABLE: [f32; 32] = [
+    0.880939, 0.914915, 0.94575, 0.973976, 1.0, 1.024141, 1.046654, 1.067743, 1.087579, 1.124031, 1.156891, 1.186804, 1.214256, 1.239621, 1.274384,
+    1.315732, 1.352519, 1.385651, 1.41579, 1.443431, 1.468957, 1.49267, 1.514809, 1.53557, 1.555116, 1.57358, 1.591076, 1.607701, 1.623537, 1.638655,
+    1.653118, 1.666981,
+];
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+// Grayscale frame fed into the sensor, 0 is black and 255 is white
+#[derive(Clone)]
+pub struct CameraImage {
+    pixels: Vec<u8>,
+}
+
+impl CameraImage {
+    pub fn test_pattern() -> Self {
+        let mut pixels = vec![0; SENSOR_WIDTH * SENSOR_HEIGHT];
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let gradient = (x + y) * 255 / (SENSOR_WIDTH + SENSOR_HEIGHT - 2);
+                let checker = if (x / 16 + y / 16) % 2 == 0 { 32 } else { 0 };
+                let (dx, dy) = (x as i32 - 64, y as i32 - 56);
+                let ring = if (dx * dx + dy * dy - 900).abs() < 120 { 0 } else { gradient + checker };
+                pixels[y * SENSOR_WIDTH + x] = ring.min(255) as u8;
+            }
+        }
+        CameraImage { pixels }
+    }
+
+    // Scales and centre crops any grayscale image to the sensor resolution
+    pub fn from_luma(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, CartridgeError> {
+        let image = image::GrayImage::from_raw(width, height, pixels)
+            .ok_or_else(|| CartridgeError::CameraImageFailure("Pixel data does not match dimensions".to_string()))?;
+        Ok(Self::from_gray_image(image))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, CartridgeError> {
+        let image = image::open(path).map_err(|error| CartridgeError::CameraImageFailure(error.to_string()))?;
+        Ok(Self::from_gray_image(image.to_luma8()))
+    }
+
+    fn from_gray_image(image: image::GrayImage) -> Self {
+        let (width, height) = image.dimensions();
+        let target_ratio = SENSOR_WIDTH as f32 / SENSOR_HEIGHT as f32;
+        let (crop_width, crop_height) = if width as f32 / height as f32 > target_ratio {
+            ((height as f32 * target_ratio) as u32, height)
+        } else {
+            (width, (width as f32 / target_ratio) as u32)
+        };
+        let cropped = imageops::crop_imm(
+            &image,
+            (width - crop_width) / 2,
+            (height - crop_height) / 2,
+            crop_width.max(1),
+            crop_height.max(1),
+        )
+        .to_image();
+        let resized = imageops::resize(&cropped, SENSOR_WIDTH as u32, SENSOR_HEIGHT as u32, FilterType::Triangle);
+        CameraImage { pixels: resized.into_raw() }
+    }
+
+    fn pixel(&self, x: i32, y: i32) -> i32 {
+        let x = x.clamp(0, SENSOR_WIDTH as i32 - 1) as usize;
+        let y = y.clamp(0, SENSOR_HEIGHT as i32 - 1) as usize;
+        self.pixels[y * SENSOR_WIDTH + x] as i32
+    }
+}
+
+// Mitsubishi M64282FP artificial retina as wired up in the Pocket Camera
+pub struct CameraSensor {
+    registers: [u8; SENSOR_REGISTERS],
+    image: CameraImage,
+    capture_cycles: u32,
+}
+
+impl CameraSensor {
+    pub fn new() -> Self {
+        CameraSensor {
+            registers: [0; SENSOR_REGISTERS],
+            image: CameraImage::test_pattern(),
+            capture_cycles: 0,
+        }
+    }
+
+    pub fn set_image(&mut self, image: CameraImage) {
+        self.image = image;
+    }
+
+    pub fn busy(&self) -> bool {
+        self.capture_cycles > 0
+    }
+
+    pub fn read_register(&self, register: usize) -> u8 {
+        match register {
+            0x00 => self.registers[0] & 0x06 | self.busy() as u8,
+            _ => 0x00,
+        }
+    }
+
+    pub fn write_register(&mut self, register: usize, value: u8) {
+        match register {
+            0x00 => {
+                self.registers[0] = value & 0x07;
+                if value & 0x01 != 0 && !self.busy() {
+                    let edge_cycles = if self.registers[1] & 0x80 == 0 { EDGE_EXTRA_CYCLES } else { 0 };
+                    self.capture_cycles = BASE_CAPTURE_CYCLES + edge_cycles + self.exposure() * 64;
+                } else if value & 0x01 == 0 {
+                    self.capture_cycles = 0;
+                }
+            }
+            0x01..SENSOR_REGISTERS => self.registers[register] = value,
+            _ => {}
+        }
+    }
+
+    // Returns true once a capture has finished and the picture should be written to RAM
+    pub fn cycle(&mut self, cycles: u32) -> bool {
+        if !self.busy() {
+            return false;
+        }
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles);
+        if self.capture_cycles == 0 {
+            self.registers[0] &= !0x01;
+            return true;
+        }
+        false
+    }
+
+    fn exposure(&self) -> u32 {
+        (self.registers[2] as u32) << 8 | self.registers[3] as u32
+    }
+
+    fn processed_pixel(&self, x: i32, y: i32) -> f32 {
+        let mut value = self.image.pixel(x, y);
+        if self.registers[4] & 0x08 != 0 {
+            value = 255 - value;
+        }
+        let gain = GAIN_TABLE[(self.registers[1] & 0x1F) as usize];
+        value as f32 * gain * self.exposure() as f32 / 0x1000 as f32
+    }
+
+    // Produces the 2bpp tile data the sensor leaves in RAM after a capture
+    pub fn capture(&self) -> Vec<u8> {
+        // VH picks the directions edges are enhanced in, N only lengthens the capture
+        let (horizontal, vertical) = match (self.registers[1] >> 5) & 0x03 {
+            0b00 => (false, false),
+            0b01 => (true, false),
+            0b10 => (false, true),
+            _ => (true, true),
+        };
+        let edge_ratio = EDGE_RATIOS[((self.registers[4] >> 4) & 0x07) as usize];
+        let mut tiles = vec![0; IMAGE_SIZE];
+
+        for y in 0..SENSOR_HEIGHT as i32 {
+            for x in 0..SENSOR_WIDTH as i32 {
+                let mut value = self.processed_pixel(x, y);
+                let mut edge = 0.0;
+                if horizontal {
+                    edge += value * 2.0 - self.processed_pixel(x - 1, y) - self.processed_pixel(x + 1, y);
+                }
+                if vertical {
+                    edge += value * 2.0 - self.processed_pixel(x, y - 1) - self.processed_pixel(x, y + 1);
+                }
+                value += edge * edge_ratio;
+
+                let matrix = DITHER_MATRIX_START + (((y % 4) * 4 + (x % 4)) * 3) as usize;
+                let color = if value < self.registers[matrix] as f32 {
+                    3
+                } else if value < self.registers[matrix + 1] as f32 {
+                    2
+                } else if value < self.registers[matrix + 2] as f32 {
+                    1
+                } else {
+                    0
+                };
+
+                let tile = (y as usize / 8) * (SENSOR_WIDTH / 8) + x as usize / 8;
+                let offset = tile * 16 + (y as usize % 8) * 2;
+                let bit = 7 - (x as usize % 8);
+                tiles[offset] |= (color & 0x01) << bit;
+                tiles[offset + 1] |= ((color >> 1) & 0x01) << bit;
+            }
+        }
+        tiles
+    }
+}
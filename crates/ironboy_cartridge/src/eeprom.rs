@@ -0,0 +1,180 @@
+const WORD_COUNT: usize = 128;
+const COMMAND_BITS: u8 = 10;
+const DATA_BITS: u8 = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Operation {
+    Write(usize),
+    WriteAll,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum State {
+    Idle,
+    Command,
+    Read(usize),
+    Receive(Operation),
+}
+
+// 93LC56 serial EEPROM in 16 bit organisation, driven bit by bit through the MBC7 registers
+pub struct Eeprom {
+    data: [u16; WORD_COUNT],
+    state: State,
+    shift_register: u16,
+    bit_count: u8,
+    write_enabled: bool,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+    updated: bool,
+}
+
+impl Eeprom {
+    pub fn new() -> Self {
+        Eeprom {
+            data: [0xFFFF; WORD_COUNT],
+            state: State::Idle,
+            shift_register: 0,
+            bit_count: 0,
+            write_enabled: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            updated: false,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        (self.chip_select as u8) << 7 | (self.clock as u8) << 6 | (self.data_in as u8) << 1 | self.data_out as u8
+    }
+
+    pub fn write(&mut self, value: u8) {
+        let chip_select = value & 0x80 != 0;
+        let clock = value & 0x40 != 0;
+        self.data_in = value & 0x02 != 0;
+
+        if !chip_select {
+            self.state = State::Idle;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.clock_rising_edge();
+        }
+
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    fn clock_rising_edge(&mut self) {
+        let bit = self.data_in as u16;
+        match self.state {
+            State::Idle => {
+                if bit == 1 {
+                    self.state = State::Command;
+                    self.shift_register = 0;
+                    self.bit_count = 0;
+                }
+            }
+            State::Command => {
+                self.shift_register = (self.shift_register << 1) | bit;
+                self.bit_count += 1;
+                if self.bit_count == COMMAND_BITS {
+                    self.execute_command();
+                }
+            }
+            State::Read(address) => {
+                self.data_out = self.shift_register & 0x8000 != 0;
+                self.shift_register <<= 1;
+                self.bit_count += 1;
+                if self.bit_count == DATA_BITS {
+                    // Sequential read continues with the next word while chip select stays high
+                    let next = (address + 1) % WORD_COUNT;
+                    self.shift_register = self.data[next];
+                    self.bit_count = 0;
+                    self.state = State::Read(next);
+                }
+            }
+            State::Receive(operation) => {
+                self.shift_register = (self.shift_register << 1) | bit;
+                self.bit_count += 1;
+                if self.bit_count == DATA_BITS {
+                    if self.write_enabled {
+                        match operation {
+                            Operation::Write(address) => self.data[address] = self.shift_register,
+                            Operation::WriteAll => self.data.fill(self.shift_register),
+                        }
+                        self.updated = true;
+                    }
+                    self.data_out = true;
+                    self.state = State::Idle;
+                }
+            }
+        }
+    }
+
+    fn execute_command(&mut self) {
+        let opcode = (self.shift_register >> 8) & 0x03;
+        // A7 and A6 of the 8-bit address field select the commands sharing opcode 00, only A6-A0 index a word
+        let sub_opcode = (self.shift_register >> 6) & 0x03;
+        let address = (self.shift_register & 0x7F) as usize;
+        self.shift_register = 0;
+        self.bit_count = 0;
+
+        self.state = match opcode {
+            0b10 => {
+                // A dummy zero bit precedes the data
+                self.data_out = false;
+                self.shift_register = self.data[address];
+                State::Read(address)
+            }
+            0b01 => State::Receive(Operation::Write(address)),
+            0b11 => {
+                if self.write_enabled {
+                    self.data[address] = 0xFFFF;
+                    self.updated = true;
+                }
+                State::Idle
+            }
+            _ => match sub_opcode {
+                0b11 => {
+                    self.write_enabled = true;
+                    State::Idle
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    State::Idle
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.data.fill(0xFFFF);
+                        self.updated = true;
+                    }
+                    State::Idle
+                }
+                _ => State::Receive(Operation::WriteAll),
+            },
+        };
+    }
+
+    pub fn updated(&mut self) -> bool {
+        let result = self.updated;
+        self.updated = false;
+        result
+    }
+
+    pub fn load(&mut self, data: &[u8]) -> bool {
+        if data.len() != WORD_COUNT * 2 {
+            return false;
+        }
+
+        for (word, bytes) in self.data.iter_mut().zip(data.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        true
+    }
+
+    pub fn dump(&self) -> Vec<u8> {
+        self.data.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
@@ -1,6 +1,6 @@
-use std::str::from_utf8;
+use crate::{CartridgeError, GameBoyMode};
 
-use crate::GameBoyMode;
+pub const HEADER_END: usize = 0x014F;
 
 // May use these at some point
 #[allow(dead_code)]
@@ -8,6 +8,7 @@ pub struct Header {
     entry: [u8; 4],
     logo: [u8; 48],
     title: String,
+    manufacturer_code: Option<String>,
     cgb_flag: u8,
     new_licensee_code: [u8; 2],
     sgb_flag: u8,
@@ -27,6 +28,7 @@ impl Default for Header {
             entry: [0; 4],
             logo: [0; 48],
             title: Default::default(),
+            manufacturer_code: None,
             cgb_flag: 0,
             new_licensee_code: [0; 2],
             sgb_flag: 0,
@@ -43,12 +45,29 @@ impl Default for Header {
 }
 
 impl Header {
-    pub fn load(bytes: &[u8]) -> Self {
-        Header {
+    pub fn load(bytes: &[u8]) -> Result<Self, CartridgeError> {
+        if bytes.len() <= HEADER_END {
+            return Err(CartridgeError::RomTooSmall(bytes.len()));
+        }
+
+        let cgb_flag = bytes[0x0143];
+        // Later cartridges shortened the title to make room for the manufacturer code and CGB flag
+        let manufacturer_code = match cgb_flag & 0x80 != 0 && bytes[0x013F..=0x0142].iter().all(u8::is_ascii_alphanumeric) {
+            true => Some(String::from_utf8_lossy(&bytes[0x013F..=0x0142]).into_owned()),
+            false => None,
+        };
+        let title_end = match (cgb_flag & 0x80 != 0, manufacturer_code.is_some()) {
+            (_, true) => 0x013E,
+            (true, false) => 0x0142,
+            (false, false) => 0x0143,
+        };
+
+        Ok(Header {
             entry: bytes[0x0100..=0x0103].try_into().unwrap(),
             logo: bytes[0x0104..=0x0133].try_into().unwrap(),
-            title: from_utf8(&bytes[0x0134..=0x0143]).unwrap_or("NO NAME").to_owned(),
-            cgb_flag: bytes[0x0143],
+            title: parse_title(&bytes[0x0134..=title_end]),
+            manufacturer_code,
+            cgb_flag,
             new_licensee_code: bytes[0x0144..=0x0145].try_into().unwrap(),
             sgb_flag: bytes[0x0146],
             cartridge_type: bytes[0x0147],
@@ -59,13 +78,53 @@ impl Header {
             version: bytes[0x014C],
             checksum: bytes[0x014D],
             global_checksum: (bytes[0x014E] as u16) << 8 | bytes[0x014F] as u16,
-        }
+        })
     }
 
     pub fn title(&self) -> &str {
         self.title.as_str()
     }
 
+    pub fn manufacturer_code(&self) -> Option<&str> {
+        self.manufacturer_code.as_deref()
+    }
+
+    pub fn cgb_flag(&self) -> u8 {
+        self.cgb_flag
+    }
+
+    pub fn sgb_flag(&self) -> u8 {
+        self.sgb_flag
+    }
+
+    pub fn old_licensee_code(&self) -> u8 {
+        self.old_licensee_code
+    }
+
+    pub fn new_licensee_code(&self) -> String {
+        String::from_utf8_lossy(&self.new_licensee_code).into_owned()
+    }
+
+    pub fn rom_size(&self) -> u8 {
+        self.rom_size
+    }
+
+    pub fn ram_size(&self) -> u8 {
+        self.ram_size
+    }
+
+    pub fn destination_code(&self) -> u8 {
+        self.destination_code
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn global_checksum(&self) -> u16 {
+        self.global_checksum
+    }
+
     pub fn mode(&self) -> GameBoyMode {
         match self.cgb_flag {
             0xC0 => GameBoyMode::Color,
@@ -74,11 +133,7 @@ impl Header {
     }
 
     pub fn rom_banks(&self) -> usize {
-        if self.rom_size <= 8 {
-            2 << self.rom_size
-        } else {
-            0
-        }
+        if self.rom_size <= 8 { 2 << self.rom_size } else { 0 }
     }
 
     pub fn ram_banks(&self) -> usize {
@@ -93,18 +148,22 @@ impl Header {
 
     pub fn has_ram(&self) -> bool {
         match self.cartridge_type {
-            0x02 | 0x03 | 0x0F | 0x10 | 0x12 | 0x13 | 0x1A | 0x1B | 0x1D | 0x1E => true,
+            0x02 | 0x03 | 0x0F | 0x10 | 0x12 | 0x13 | 0x1A | 0x1B | 0x1D | 0x1E | 0xFC => true,
             _ => false,
         }
     }
 
     pub fn has_battery(&self) -> bool {
         match self.cartridge_type {
-            0x03 | 0x06 | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E => true,
+            0x03 | 0x06 | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFC => true,
             _ => false,
         }
     }
 
+    pub fn has_rumble(&self) -> bool {
+        matches!(self.cartridge_type, 0x1C..=0x1E)
+    }
+
     pub fn has_real_time_clock(&self) -> bool {
         match self.cartridge_type {
             0x0F | 0x10 => true,
@@ -112,3 +171,30 @@ impl Header {
         }
     }
 }
+
+pub fn header_checksum(bytes: &[u8]) -> u8 {
+    bytes[0x0134..=0x014C]
+        .iter()
+        .fold(0u8, |checksum, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+// Sum of every ROM byte except the two checksum bytes themselves
+pub fn global_checksum(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(address, _)| *address != 0x014E && *address != 0x014F)
+        .fold(0u16, |checksum, (_, byte)| checksum.wrapping_add(*byte as u16))
+}
+
+fn parse_title(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    let title: String = bytes[..end]
+        .iter()
+        .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '?' })
+        .collect();
+    match title.trim_end() {
+        "" => "NO NAME".to_string(),
+        title => title.to_string(),
+    }
+}
@@ -3,7 +3,9 @@ use mbc1::Mbc1;
 use mbc2::Mbc2;
 use mbc3::Mbc3;
 use mbc5::Mbc5;
+use mbc7::Mbc7;
 use no_mbc::NoMbc;
+use pocket_camera::PocketCamera;
 use thiserror::Error;
 
 use self::header::Header;
@@ -13,13 +15,24 @@ use std::{
     path::PathBuf,
 };
 
+pub use camera_sensor::CameraImage;
+pub use rom_info::{CgbSupport, Licensee, LoadMode, RomInfo};
+
+mod camera_sensor;
+mod eeprom;
 mod header;
+mod licensee;
 mod mbc1;
 mod mbc2;
 mod mbc3;
 mod mbc5;
+mod mbc7;
 mod no_mbc;
+mod pocket_camera;
+mod rom_info;
 mod rtc;
+#[cfg(test)]
+mod tests;
 
 pub trait MemoryBankController {
     fn read_rom(&self, address: u16) -> u8;
@@ -30,12 +43,26 @@ pub trait MemoryBankController {
     fn dump_ram(&self) -> Vec<u8>;
     fn ram_updated(&mut self) -> bool;
     fn has_battery(&self) -> bool;
+
+    // Accelerometer input in the range -1.0..=1.0 per axis, only used by MBC7
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    // Advances hardware on the cartridge itself, e.g. the Pocket Camera sensor
+    fn cycle(&mut self, _cycles: u32) {}
+
+    // Frame captured by the next Pocket Camera exposure
+    fn set_camera_image(&mut self, _image: CameraImage) {}
+
+    // State of the rumble motor on MBC5 rumble cartridges
+    fn rumble_active(&self) -> bool {
+        false
+    }
 }
 
 pub struct Cartridge {
     pub mbc: Box<dyn MemoryBankController>,
-    title: String,
     mode: GameBoyMode,
+    info: RomInfo,
     ram_file: PathBuf,
 }
 
@@ -45,8 +72,8 @@ impl Default for Cartridge {
             mbc: NoMbc::new(vec![0; 0xFFFF])
                 .map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>)
                 .unwrap(),
-            title: String::new(),
             mode: GameBoyMode::Color,
+            info: RomInfo::parse(&[0; 0x0150]).unwrap(),
             ram_file: PathBuf::new(),
         }
     }
@@ -54,23 +81,30 @@ impl Default for Cartridge {
 
 impl Cartridge {
     pub fn load(rom_file: PathBuf, buffer: Vec<u8>) -> Result<Cartridge, CartridgeError> {
-        let header = Header::load(&buffer[0x000..=0x014F]);
+        Self::load_with_mode(rom_file, buffer, LoadMode::Strict)
+    }
+
+    pub fn load_with_mode(rom_file: PathBuf, buffer: Vec<u8>, load_mode: LoadMode) -> Result<Cartridge, CartridgeError> {
+        let header = Header::load(&buffer)?;
+        let info = RomInfo::from_header(&header, &buffer);
 
-        let mut checksum: u8 = 0;
-        for address in 0x0134..=0x014C {
-            checksum = checksum.wrapping_sub(buffer[address]).wrapping_sub(1)
+        // Everything else the header gets wrong is only reported through `RomInfo::warnings`
+        if load_mode == LoadMode::Strict && !info.header_checksum_valid() {
+            return Err(CartridgeError::CheckSumFailure);
         }
 
-        match checksum == header.checksum {
-            true => Ok(()),
-            false => Err(CartridgeError::CheckSumFailure),
-        }?;
+        // Fall back to the file size when the header does not describe the ROM
+        let rom_banks = match header.rom_banks() {
+            0 => buffer.len().div_ceil(0x4000).next_power_of_two().max(2),
+            banks => banks,
+        };
 
         let mut mbc = match header.cartridge_type {
             0x00 => NoMbc::new(buffer).map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
-            0x01..=0x03 => Mbc1::new(buffer, header.rom_banks(), header.ram_banks(), header.has_battery())
-                .map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
-            0x05..=0x06 => Mbc2::new(buffer, header.rom_banks(), header.has_battery()).map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
+            0x01..=0x03 => {
+                Mbc1::new(buffer, rom_banks, header.ram_banks(), header.has_battery()).map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>)
+            }
+            0x05..=0x06 => Mbc2::new(buffer, rom_banks, header.has_battery()).map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
             0x0F..=0x13 => Mbc3::new(
                 buffer,
                 header.ram_banks(),
@@ -79,7 +113,10 @@ impl Cartridge {
                 header.has_real_time_clock(),
             )
             .map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
-            0x19..=0x1E => Mbc5::new(buffer, header.rom_banks(), header.ram_banks(), header.has_battery())
+            0x19..=0x1E => Mbc5::new(buffer, rom_banks, header.ram_banks(), header.has_battery(), header.has_rumble())
+                .map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
+            0x22 => Mbc7::new(buffer, rom_banks, header.has_battery()).map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
+            0xFC => PocketCamera::new(buffer, rom_banks, header.ram_banks(), header.has_battery())
                 .map(|mbc| Box::new(mbc) as Box<dyn MemoryBankController>),
             _ => Err(CartridgeError::InvalidCatridgeType),
         }?;
@@ -103,15 +140,19 @@ impl Cartridge {
 
         let cartridge = Cartridge {
             mbc,
-            title: header.title().to_string(),
             mode: header.mode(),
+            info,
             ram_file,
         };
         Ok(cartridge)
     }
 
     pub fn title(&self) -> &str {
-        self.title.as_str()
+        self.info.title.as_str()
+    }
+
+    pub fn info(&self) -> &RomInfo {
+        &self.info
     }
 
     pub fn mode(&self) -> GameBoyMode {
@@ -131,10 +172,20 @@ impl Drop for Cartridge {
     }
 }
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum CartridgeError {
     #[error("Cartridge checksum not valid")]
     CheckSumFailure,
+    #[error("Cartridge global checksum not valid")]
+    GlobalCheckSumFailure,
+    #[error("ROM of `{0}` bytes is too small to contain a cartridge header")]
+    RomTooSmall(usize),
+    #[error("Unknown ROM size code `{0:#04X}`")]
+    InvalidRomSize(u8),
+    #[error("Unknown RAM size code `{0:#04X}`")]
+    InvalidRamSize(u8),
+    #[error("Header declares `{header}` bytes of ROM but the file has `{file}`")]
+    RomSizeMismatch { header: usize, file: usize },
     #[error("Unsupported Cartridge type")]
     InvalidCatridgeType,
     #[error("Error reading save")]
@@ -143,4 +194,6 @@ pub enum CartridgeError {
     IncorrectLengthLoaded,
     #[error("Save file failed with error: `{0}`")]
     SaveFileFailure(String),
+    #[error("Camera image could not be loaded: `{0}`")]
+    CameraImageFailure(String),
 }
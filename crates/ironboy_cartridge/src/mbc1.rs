@@ -77,7 +77,7 @@ impl MemoryBankController for Mbc1 {
             return 0xFF;
         }
         let rambank = if self.banking_mode == 1 { self.current_ram_bank } else { 0 };
-        self.ram[(rambank * 0x2000) | ((address & 0x1FFF) as usize)]
+        *self.ram.get((rambank * 0x2000) | ((address & 0x1FFF) as usize)).unwrap_or(&0xFF)
     }
 
     fn write_ram(&mut self, address: u16, value: u8) {
@@ -1,4 +1,3 @@
-use std::convert::TryInto;
 use std::io::prelude::*;
 
 use super::rtc::RealTimeClock;
@@ -64,7 +63,7 @@ impl MemoryBankController for Mbc3 {
                 self.current_ram_bank = (value & 0x7) as usize;
             }
             0x6000..=0x7FFF => self.rtc.set_latch_registers(),
-            _ => panic!("Could not write to {:04X} (MBC3)", address),
+            _ => {}
         }
     }
 
@@ -108,11 +107,15 @@ impl MemoryBankController for Mbc3 {
             return Err(CartridgeError::IncorrectLengthLoaded);
         }
 
-        let (int_bytes, rest) = data.split_at(8);
-        let time = u64::from_be_bytes(int_bytes.try_into().unwrap());
-        if self.rtc.time().is_some() {
-            self.rtc.load_time(Some(time));
-        }
+        let rest = match data.split_first_chunk::<8>() {
+            Some((time, rest)) if data.len() == self.ram.len() + 8 => {
+                if self.rtc.time().is_some() {
+                    self.rtc.load_time(Some(u64::from_be_bytes(*time)));
+                }
+                rest
+            }
+            _ => data,
+        };
         self.ram = rest.to_vec();
         Ok(())
     }
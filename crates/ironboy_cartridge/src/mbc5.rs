@@ -10,10 +10,12 @@ pub struct Mbc5 {
     rom_banks: usize,
     ram_banks: usize,
     has_battery: bool,
+    has_rumble: bool,
+    rumble_active: bool,
 }
 
 impl Mbc5 {
-    pub fn new(buffer: Vec<u8>, rom_banks: usize, ram_banks: usize, has_battery: bool) -> Result<Mbc5, CartridgeError> {
+    pub fn new(buffer: Vec<u8>, rom_banks: usize, ram_banks: usize, has_battery: bool, has_rumble: bool) -> Result<Mbc5, CartridgeError> {
         let mbc = Mbc5 {
             rom: buffer,
             ram: vec![0; ram_banks * 0x2000],
@@ -24,6 +26,8 @@ impl Mbc5 {
             rom_banks,
             ram_banks,
             has_battery,
+            has_rumble,
+            rumble_active: false,
         };
         Ok(mbc)
     }
@@ -44,7 +48,12 @@ impl MemoryBankController for Mbc5 {
             0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
             0x2000..=0x2FFF => self.current_rom_bank = ((self.current_rom_bank & 0x100) | (value as usize)) % self.rom_banks,
             0x3000..=0x3FFF => self.current_rom_bank = ((self.current_rom_bank & 0x0FF) | (((value & 0x1) as usize) << 8)) % self.rom_banks,
-            0x4000..=0x5FFF => self.current_ram_bank = ((value & 0x0F) as usize) % self.ram_banks,
+            0x4000..=0x5FFF if self.has_rumble => {
+                // Bit 3 drives the rumble motor instead of selecting a RAM bank
+                self.rumble_active = value & 0x08 != 0;
+                self.current_ram_bank = ((value & 0x07) as usize) % self.ram_banks.max(1);
+            }
+            0x4000..=0x5FFF => self.current_ram_bank = ((value & 0x0F) as usize) % self.ram_banks.max(1),
             _ => {}
         }
     }
@@ -53,15 +62,17 @@ impl MemoryBankController for Mbc5 {
         if !self.ram_enabled {
             return 0;
         }
-        self.ram[self.current_ram_bank * 0x2000 | ((address as usize) & 0x1FFF)]
+        *self.ram.get(self.current_ram_bank * 0x2000 | ((address as usize) & 0x1FFF)).unwrap_or(&0xFF)
     }
 
     fn write_ram(&mut self, address: u16, value: u8) {
         if self.ram_enabled == false {
             return;
         }
-        self.ram[self.current_ram_bank * 0x2000 | ((address as usize) & 0x1FFF)] = value;
-        self.ram_updated = true;
+        if let Some(byte) = self.ram.get_mut(self.current_ram_bank * 0x2000 | ((address as usize) & 0x1FFF)) {
+            *byte = value;
+            self.ram_updated = true;
+        }
     }
 
     fn load_ram(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
@@ -86,4 +97,8 @@ impl MemoryBankController for Mbc5 {
     fn has_battery(&self) -> bool {
         self.has_battery
     }
+
+    fn rumble_active(&self) -> bool {
+        self.rumble_active
+    }
 }
@@ -0,0 +1,147 @@
+use super::eeprom::Eeprom;
+use super::{CartridgeError, MemoryBankController};
+
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+const ACCELEROMETER_GRAVITY: f32 = 0x70 as f32;
+const ACCELEROMETER_LATCH_RESET: u16 = 0x8000;
+
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    eeprom: Eeprom,
+    ram_enabled: bool,
+    registers_enabled: bool,
+    current_rom_bank: usize,
+    rom_banks: usize,
+    has_battery: bool,
+    tilt: (f32, f32),
+    latch_armed: bool,
+    latched: bool,
+    x_latch: u16,
+    y_latch: u16,
+}
+
+impl Mbc7 {
+    pub fn new(buffer: Vec<u8>, rom_banks: usize, has_battery: bool) -> Result<Mbc7, CartridgeError> {
+        let mbc = Mbc7 {
+            rom: buffer,
+            eeprom: Eeprom::new(),
+            ram_enabled: false,
+            registers_enabled: false,
+            current_rom_bank: 1,
+            rom_banks,
+            has_battery,
+            tilt: (0.0, 0.0),
+            latch_armed: false,
+            latched: false,
+            x_latch: ACCELEROMETER_LATCH_RESET,
+            y_latch: ACCELEROMETER_LATCH_RESET,
+        };
+        Ok(mbc)
+    }
+
+    fn latch_accelerometer(&mut self) {
+        // Tilting right lowers X, tilting towards the player raises Y
+        let (x, y) = self.tilt;
+        self.x_latch = (ACCELEROMETER_CENTER - x * ACCELEROMETER_GRAVITY) as u16;
+        self.y_latch = (ACCELEROMETER_CENTER + y * ACCELEROMETER_GRAVITY) as u16;
+        self.latched = true;
+    }
+
+    fn read_register(&self, register: u16) -> u8 {
+        match register {
+            0x2 => self.x_latch as u8,
+            0x3 => (self.x_latch >> 8) as u8,
+            0x4 => self.y_latch as u8,
+            0x5 => (self.y_latch >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            0x0 if value == 0x55 => {
+                self.latched = false;
+                self.latch_armed = true;
+                self.x_latch = ACCELEROMETER_LATCH_RESET;
+                self.y_latch = ACCELEROMETER_LATCH_RESET;
+            }
+            0x1 => {
+                if value == 0xAA && self.latch_armed && !self.latched {
+                    self.latch_accelerometer();
+                }
+                self.latch_armed = false;
+            }
+            0x8 => self.eeprom.write(value),
+            _ => {}
+        }
+    }
+}
+
+impl MemoryBankController for Mbc7 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        };
+        let address = (bank * 0x4000) | ((address as usize) & 0x3FFF);
+        *self.rom.get(address).unwrap_or(&0xFF)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+                if !self.ram_enabled {
+                    self.registers_enabled = false;
+                }
+            }
+            0x2000..=0x3FFF => self.current_rom_bank = (value as usize) % self.rom_banks.max(1),
+            0x4000..=0x5FFF => self.registers_enabled = self.ram_enabled && value == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || !self.registers_enabled {
+            return 0xFF;
+        }
+        match address {
+            0xA000..=0xAFFF => self.read_register((address >> 4) & 0x0F),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled || !self.registers_enabled {
+            return;
+        }
+        if let 0xA000..=0xAFFF = address {
+            self.write_register((address >> 4) & 0x0F, value);
+        }
+    }
+
+    fn load_ram(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        match self.eeprom.load(data) {
+            true => Ok(()),
+            false => Err(CartridgeError::IncorrectLengthLoaded),
+        }
+    }
+
+    fn dump_ram(&self) -> Vec<u8> {
+        self.eeprom.dump()
+    }
+
+    fn ram_updated(&mut self) -> bool {
+        self.eeprom.updated()
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+    }
+}
@@ -12,7 +12,7 @@ impl NoMbc {
 
 impl MemoryBankController for NoMbc {
     fn read_rom(&self, address: u16) -> u8 {
-        self.rom[address as usize]
+        *self.rom.get(address as usize).unwrap_or(&0xFF)
     }
 
     fn write_rom(&mut self, _address: u16, _value: u8) {
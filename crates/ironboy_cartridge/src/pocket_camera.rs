@@ -0,0 +1,123 @@
+use super::camera_sensor::{CameraImage, CameraSensor, IMAGE_SIZE};
+use super::{CartridgeError, MemoryBankController};
+
+const IMAGE_OFFSET: usize = 0x0100;
+
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    sensor: CameraSensor,
+    ram_enabled: bool,
+    ram_updated: bool,
+    registers_selected: bool,
+    current_rom_bank: usize,
+    current_ram_bank: usize,
+    rom_banks: usize,
+    ram_banks: usize,
+    has_battery: bool,
+}
+
+impl PocketCamera {
+    pub fn new(buffer: Vec<u8>, rom_banks: usize, ram_banks: usize, has_battery: bool) -> Result<PocketCamera, CartridgeError> {
+        let mbc = PocketCamera {
+            rom: buffer,
+            ram: vec![0; ram_banks * 0x2000],
+            sensor: CameraSensor::new(),
+            ram_enabled: false,
+            ram_updated: false,
+            registers_selected: false,
+            current_rom_bank: 1,
+            current_ram_bank: 0,
+            rom_banks,
+            ram_banks,
+            has_battery,
+        };
+        Ok(mbc)
+    }
+}
+
+impl MemoryBankController for PocketCamera {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        };
+        let address = (bank * 0x4000) | ((address as usize) & 0x3FFF);
+        *self.rom.get(address).unwrap_or(&0xFF)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.current_rom_bank = ((value & 0x3F) as usize) % self.rom_banks.max(1),
+            0x4000..=0x5FFF => {
+                self.registers_selected = value & 0x10 != 0;
+                self.current_ram_bank = ((value & 0x0F) as usize) % self.ram_banks.max(1);
+            }
+            _ => {}
+        }
+    }
+
+    // Unlike other controllers the RAM stays readable while writes are disabled
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_selected {
+            return self.sensor.read_register((address & 0x7F) as usize);
+        }
+        if self.sensor.busy() {
+            return 0x00;
+        }
+        *self
+            .ram
+            .get((self.current_ram_bank * 0x2000) | ((address as usize) & 0x1FFF))
+            .unwrap_or(&0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.registers_selected {
+            self.sensor.write_register((address & 0x7F) as usize, value);
+            return;
+        }
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(byte) = self.ram.get_mut((self.current_ram_bank * 0x2000) | ((address as usize) & 0x1FFF)) {
+            *byte = value;
+            self.ram_updated = true;
+        }
+    }
+
+    fn load_ram(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        if data.len() != self.ram.len() {
+            return Err(CartridgeError::IncorrectLengthLoaded);
+        }
+
+        self.ram = data.to_vec();
+        Ok(())
+    }
+
+    fn dump_ram(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn ram_updated(&mut self) -> bool {
+        let result = self.ram_updated;
+        self.ram_updated = false;
+        result
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn cycle(&mut self, cycles: u32) {
+        if self.sensor.cycle(cycles) && self.ram.len() >= IMAGE_OFFSET + IMAGE_SIZE {
+            let image = self.sensor.capture();
+            self.ram[IMAGE_OFFSET..IMAGE_OFFSET + IMAGE_SIZE].copy_from_slice(&image);
+            self.ram_updated = true;
+        }
+    }
+
+    fn set_camera_image(&mut self, image: CameraImage) {
+        self.sensor.set_image(image);
+    }
+}
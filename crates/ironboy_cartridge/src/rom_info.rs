@@ -0,0 +1,155 @@
+use crate::{
+    CartridgeError,
+    header::{self, Header},
+    licensee,
+};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LoadMode {
+    // Reject ROMs with an invalid header checksum, other header problems are only warnings
+    Strict,
+    // Report header problems as warnings and run the ROM anyway, useful for homebrew and patched ROMs
+    Lenient,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CgbSupport {
+    None,
+    Enhanced,
+    Only,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Licensee {
+    Old(u8),
+    New(String),
+}
+
+impl Licensee {
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Licensee::Old(code) => licensee::old_licensee_name(*code),
+            Licensee::New(code) => licensee::new_licensee_name(code),
+        }
+    }
+}
+
+// Everything the cartridge header says about a ROM, plus how well the ROM matches it
+#[derive(Debug, Clone)]
+pub struct RomInfo {
+    pub title: String,
+    // Unprocessed title area, the CGB boot ROM hashes it to pick a palette for DMG games
+    pub raw_title: [u8; 16],
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub licensee: Licensee,
+    pub old_licensee_code: u8,
+    pub cartridge_type: u8,
+    pub rom_size_code: u8,
+    pub rom_size: Option<usize>,
+    pub ram_size_code: u8,
+    pub ram_size: Option<usize>,
+    pub japanese: bool,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub computed_header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_global_checksum: u16,
+    pub file_size: usize,
+}
+
+impl RomInfo {
+    pub fn parse(buffer: &[u8]) -> Result<RomInfo, CartridgeError> {
+        let header = Header::load(buffer)?;
+        Ok(RomInfo::from_header(&header, buffer))
+    }
+
+    pub(crate) fn from_header(header: &Header, buffer: &[u8]) -> RomInfo {
+        let cgb_support = match header.cgb_flag() {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        };
+        // The SGB flag is only honoured together with the new licensee code
+        let sgb_support = header.sgb_flag() == 0x03 && header.old_licensee_code() == 0x33;
+        let licensee = match header.old_licensee_code() {
+            0x33 => Licensee::New(header.new_licensee_code()),
+            code => Licensee::Old(code),
+        };
+        let rom_size = match header.rom_banks() {
+            0 => None,
+            banks => Some(banks * 0x4000),
+        };
+        let ram_size = match header.ram_size() {
+            0x0 => Some(0),
+            0x1 => Some(0x800),
+            0x2..=0x5 => Some(header.ram_banks() * 0x2000),
+            _ => None,
+        };
+
+        RomInfo {
+            title: header.title().to_string(),
+            raw_title: buffer.get(0x0134..0x0144).and_then(|title| title.try_into().ok()).unwrap_or_default(),
+            manufacturer_code: header.manufacturer_code().map(str::to_string),
+            cgb_support,
+            sgb_support,
+            licensee,
+            old_licensee_code: header.old_licensee_code(),
+            cartridge_type: header.cartridge_type,
+            rom_size_code: header.rom_size(),
+            rom_size,
+            ram_size_code: header.ram_size(),
+            ram_size,
+            japanese: header.destination_code() == 0x00,
+            version: header.version(),
+            header_checksum: header.checksum,
+            computed_header_checksum: header::header_checksum(buffer),
+            global_checksum: header.global_checksum(),
+            computed_global_checksum: header::global_checksum(buffer),
+            file_size: buffer.len(),
+        }
+    }
+
+    pub fn header_checksum_valid(&self) -> bool {
+        self.header_checksum == self.computed_header_checksum
+    }
+
+    // Real hardware never verifies this one, so a mismatch is only informational
+    pub fn global_checksum_valid(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+
+    pub fn licensee_name(&self) -> Option<&'static str> {
+        self.licensee.name()
+    }
+
+    pub fn licensed_by_nintendo(&self) -> bool {
+        match &self.licensee {
+            Licensee::Old(code) => *code == 0x01,
+            Licensee::New(code) => code == "01",
+        }
+    }
+
+    pub fn warnings(&self) -> Vec<CartridgeError> {
+        let mut warnings = Vec::new();
+        if !self.header_checksum_valid() {
+            warnings.push(CartridgeError::CheckSumFailure);
+        }
+        if !self.global_checksum_valid() {
+            warnings.push(CartridgeError::GlobalCheckSumFailure);
+        }
+        match self.rom_size {
+            None => warnings.push(CartridgeError::InvalidRomSize(self.rom_size_code)),
+            Some(size) if size != self.file_size => warnings.push(CartridgeError::RomSizeMismatch {
+                header: size,
+                file: self.file_size,
+            }),
+            Some(_) => {}
+        }
+        if self.ram_size.is_none() {
+            warnings.push(CartridgeError::InvalidRamSize(self.ram_size_code));
+        }
+        warnings
+    }
+}
@@ -67,16 +67,14 @@ impl RealTimeClock {
         if self.time.is_none() {
             return None;
         }
-        let mut time = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
-            Ok(t) => t.as_secs(),
-            Err(_) => panic!("System clock is set to a time before the unix epoch (1970-01-01)"),
-        };
-        time -= self.registers[0] as u64;
-        time -= (self.registers[1] as u64) * 60;
-        time -= (self.registers[2] as u64) * 3600;
+        // A system clock set before the unix epoch counts as the epoch itself
+        let time = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
         let days = ((self.registers[4] as u64 & 0x1) << 8) | (self.registers[3] as u64);
-        time -= days * 3600 * 24;
-        Some(time)
+        let elapsed = self.registers[0] as u64 + (self.registers[1] as u64) * 60 + (self.registers[2] as u64) * 3600 + days * 3600 * 24;
+        Some(time.saturating_sub(elapsed))
     }
 
     pub fn time(&self) -> Option<u64> {
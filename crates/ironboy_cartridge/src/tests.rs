@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+
+use crate::{
+    Cartridge, CartridgeError, Licensee, LoadMode, MemoryBankController, RomInfo,
+    camera_sensor::{CameraImage, CameraSensor, SENSOR_HEIGHT, SENSOR_WIDTH},
+    eeprom::Eeprom,
+    header,
+    mbc3::Mbc3,
+    mbc7::Mbc7,
+    pocket_camera::PocketCamera,
+};
+
+const CHIP_SELECT: u8 = 0x80;
+const CLOCK: u8 = 0x40;
+
+fn clock_bits(eeprom: &mut Eeprom, value: u32, bits: u8) {
+    for i in (0..bits).rev() {
+        let data_in = ((value >> i) & 1) as u8 * 0x02;
+        eeprom.write(CHIP_SELECT | data_in);
+        eeprom.write(CHIP_SELECT | CLOCK | data_in);
+    }
+}
+
+// Start bit, then a 2-bit opcode and the 8-bit address field
+fn eeprom_command(eeprom: &mut Eeprom, opcode: u32, address: u32) {
+    clock_bits(eeprom, 1 << 10 | opcode << 8 | address, 11);
+}
+
+fn deselect(eeprom: &mut Eeprom) {
+    eeprom.write(0);
+}
+
+fn eeprom_write(eeprom: &mut Eeprom, address: u32, value: u16) {
+    eeprom_command(eeprom, 0b01, address);
+    clock_bits(eeprom, value as u32, 16);
+    deselect(eeprom);
+}
+
+fn eeprom_read(eeprom: &mut Eeprom, address: u32) -> u16 {
+    eeprom_command(eeprom, 0b10, address);
+    // The dummy bit
+    assert_eq!(eeprom.read() & 1, 0);
+    let mut value = 0;
+    for _ in 0..16 {
+        eeprom.write(CHIP_SELECT);
+        eeprom.write(CHIP_SELECT | CLOCK);
+        value = value << 1 | (eeprom.read() & 1) as u16;
+    }
+    deselect(eeprom);
+    value
+}
+
+fn ewen(eeprom: &mut Eeprom) {
+    eeprom_command(eeprom, 0b00, 0b1100_0000);
+    deselect(eeprom);
+}
+
+fn ewds(eeprom: &mut Eeprom) {
+    eeprom_command(eeprom, 0b00, 0b0000_0000);
+    deselect(eeprom);
+}
+
+#[test]
+fn eeprom_write_reads_back_once_enabled() {
+    let mut eeprom = Eeprom::new();
+    ewen(&mut eeprom);
+    eeprom_write(&mut eeprom, 0x05, 0x1234);
+    eeprom_write(&mut eeprom, 0x7F, 0xBEEF);
+    assert!(eeprom.updated());
+    assert_eq!(eeprom_read(&mut eeprom, 0x05), 0x1234);
+    assert_eq!(eeprom_read(&mut eeprom, 0x7F), 0xBEEF);
+    assert_eq!(eeprom_read(&mut eeprom, 0x06), 0xFFFF);
+    assert_eq!(&eeprom.dump()[0x0A..0x0C], &[0x34, 0x12]);
+}
+
+#[test]
+fn eeprom_writes_and_erases_every_word() {
+    let mut eeprom = Eeprom::new();
+    ewen(&mut eeprom);
+    // WRAL
+    eeprom_command(&mut eeprom, 0b00, 0b0100_0000);
+    clock_bits(&mut eeprom, 0xABCD, 16);
+    deselect(&mut eeprom);
+    assert!(eeprom.dump().chunks(2).all(|word| word == [0xCD, 0xAB]));
+
+    // ERAL
+    eeprom_command(&mut eeprom, 0b00, 0b1000_0000);
+    deselect(&mut eeprom);
+    assert!(eeprom.dump().iter().all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn eeprom_ignores_writes_while_disabled() {
+    let mut eeprom = Eeprom::new();
+    eeprom_write(&mut eeprom, 0x05, 0x1234);
+    ewen(&mut eeprom);
+    ewds(&mut eeprom);
+    eeprom_write(&mut eeprom, 0x06, 0x5678);
+    assert!(!eeprom.updated());
+    assert_eq!(eeprom_read(&mut eeprom, 0x05), 0xFFFF);
+    assert_eq!(eeprom_read(&mut eeprom, 0x06), 0xFFFF);
+}
+
+// MBC7 with RAM and the accelerometer registers enabled
+fn mbc7() -> Mbc7 {
+    let mut mbc = Mbc7::new(vec![0; 0x8000], 2, true).unwrap();
+    mbc.write_rom(0x0000, 0x0A);
+    mbc.write_rom(0x4000, 0x40);
+    mbc
+}
+
+fn accelerometer(mbc: &Mbc7) -> (u16, u16) {
+    let x = u16::from_le_bytes([mbc.read_ram(0xA020), mbc.read_ram(0xA030)]);
+    let y = u16::from_le_bytes([mbc.read_ram(0xA040), mbc.read_ram(0xA050)]);
+    (x, y)
+}
+
+#[test]
+fn mbc7_accelerometer_latches_after_55_then_aa() {
+    let mut mbc = mbc7();
+    assert_eq!(accelerometer(&mbc), (0x8000, 0x8000));
+    // 0xAA alone doesn't latch
+    mbc.write_ram(0xA010, 0xAA);
+    assert_eq!(accelerometer(&mbc), (0x8000, 0x8000));
+
+    // Held level
+    mbc.write_ram(0xA000, 0x55);
+    mbc.write_ram(0xA010, 0xAA);
+    assert_eq!(accelerometer(&mbc), (0x81D0, 0x81D0));
+
+    // The latched value is kept until the next 0x55 erases it
+    mbc.set_tilt(1.0, -0.5);
+    mbc.write_ram(0xA010, 0xAA);
+    assert_eq!(accelerometer(&mbc), (0x81D0, 0x81D0));
+    mbc.write_ram(0xA000, 0x55);
+    assert_eq!(accelerometer(&mbc), (0x8000, 0x8000));
+    mbc.write_ram(0xA010, 0xAA);
+    assert_eq!(accelerometer(&mbc), (0x81D0 - 0x70, 0x81D0 - 0x38));
+}
+
+#[test]
+fn mbc7_registers_read_back_fixed_values_and_the_eeprom() {
+    let mut mbc = mbc7();
+    assert_eq!(mbc.read_ram(0xA060), 0x00);
+    assert_eq!(mbc.read_ram(0xA070), 0xFF);
+    // Chip select low, DO idles high
+    assert_eq!(mbc.read_ram(0xA080), 0x01);
+    mbc.write_ram(0xA080, CHIP_SELECT | CLOCK | 0x02);
+    assert_eq!(mbc.read_ram(0xA080), CHIP_SELECT | CLOCK | 0x03);
+    for address in [0xA090, 0xA0A0, 0xA0B0] {
+        assert_eq!(mbc.read_ram(address), 0xFF);
+    }
+    // Registers repeat every 0x100 bytes up to 0xAFFF
+    assert_eq!(mbc.read_ram(0xA170), 0xFF);
+    assert_eq!(mbc.read_ram(0xAF60), 0x00);
+
+    // Nothing is readable once the second enable is cleared
+    mbc.write_rom(0x4000, 0x00);
+    assert_eq!(mbc.read_ram(0xA060), 0xFF);
+}
+
+#[test]
+fn camera_capture_stays_busy_for_the_exposure() {
+    let mut camera = PocketCamera::new(vec![0; 0x100000], 64, 16, true).unwrap();
+    camera.write_rom(0x0000, 0x0A);
+    camera.write_rom(0x4000, 0x10);
+    // Exposure of 0x0100 with the edge processing left on
+    camera.write_ram(0xA002, 0x01);
+    camera.write_ram(0xA003, 0x00);
+    camera.write_ram(0xA000, 0x01);
+    assert_eq!(camera.read_ram(0xA000), 0x01);
+
+    // 129792 base cycles, 2048 for the edge processing and 64 per exposure step
+    camera.cycle(129792 + 2048 + 0x0100 * 64 - 1);
+    assert_eq!(camera.read_ram(0xA000), 0x01);
+    camera.write_rom(0x4000, 0x00);
+    assert_eq!(camera.read_ram(0xA100), 0x00);
+    assert!(!camera.ram_updated());
+
+    camera.cycle(1);
+    assert!(camera.ram_updated());
+    camera.write_rom(0x4000, 0x10);
+    assert_eq!(camera.read_ram(0xA000), 0x00);
+}
+
+#[test]
+fn camera_capture_dithers_into_tiles() {
+    // Four vertical bands, darkest on the left
+    let pixels = (0..SENSOR_WIDTH * SENSOR_HEIGHT)
+        .map(|offset| [0x20, 0x60, 0xA0, 0xE0][offset % SENSOR_WIDTH / 32])
+        .collect();
+    let mut sensor = CameraSensor::new();
+    sensor.set_image(CameraImage::from_luma(SENSOR_WIDTH as u32, SENSOR_HEIGHT as u32, pixels).unwrap());
+    // Unity gain and exposure, no edge enhancement
+    sensor.write_register(0x01, 0x04);
+    sensor.write_register(0x02, 0x10);
+    sensor.write_register(0x03, 0x00);
+    for position in 0..16 {
+        let thresholds = if position == 0 { [0xFF; 3] } else { [0x40, 0x80, 0xC0] };
+        for (index, threshold) in thresholds.into_iter().enumerate() {
+            sensor.write_register(0x06 + position * 3 + index, threshold);
+        }
+    }
+
+    let tiles = sensor.capture();
+    let tile_row = |tile: usize, row: usize| [tiles[tile * 16 + row * 2], tiles[tile * 16 + row * 2 + 1]];
+    // The first row of each 4x4 block starts with the raised thresholds
+    assert_eq!(tile_row(0, 0), [0xFF, 0xFF]);
+    assert_eq!(tile_row(4, 0), [0x88, 0xFF]);
+    assert_eq!(tile_row(8, 0), [0xFF, 0x88]);
+    assert_eq!(tile_row(12, 0), [0x88, 0x88]);
+    assert_eq!(tile_row(12, 3), [0x00, 0x00]);
+    assert_eq!(tile_row(12, 4), [0x88, 0x88]);
+    // The last tile row, 13 rows of 16 tiles down
+    assert_eq!(tile_row(13 * 16 + 8, 7), [0xFF, 0x00]);
+}
+
+// 2-bit color the sensor left in its tile data for pixel (x, y)
+fn captured_color(tiles: &[u8], x: usize, y: usize) -> u8 {
+    let offset = ((y / 8) * (SENSOR_WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+    let bit = 7 - x % 8;
+    (tiles[offset] >> bit & 0x01) | (tiles[offset + 1] >> bit & 0x01) << 1
+}
+
+#[test]
+fn camera_edge_modes_enhance_the_selected_directions() {
+    // Dark left half and bright right half, so only horizontal neighbours differ at the border
+    let pixels = (0..SENSOR_WIDTH * SENSOR_HEIGHT)
+        .map(|offset| if offset % SENSOR_WIDTH < 64 { 0x60 } else { 0xA0 })
+        .collect();
+    let mut sensor = CameraSensor::new();
+    sensor.set_image(CameraImage::from_luma(SENSOR_WIDTH as u32, SENSOR_HEIGHT as u32, pixels).unwrap());
+    sensor.write_register(0x02, 0x10);
+    sensor.write_register(0x03, 0x00);
+    // Edge ratio of 1
+    sensor.write_register(0x04, 0x20);
+    for register in 0..16 * 3 {
+        sensor.write_register(0x06 + register, [0x40, 0x80, 0xC0][register % 3]);
+    }
+
+    // No edges, horizontal, vertical and 2D with N set, then 2D without N, all at unity gain
+    for (register, border) in [(0x04, [2, 1]), (0xA4, [3, 0]), (0xC4, [2, 1]), (0xE4, [3, 0]), (0x64, [3, 0])] {
+        sensor.write_register(0x01, register);
+        let tiles = sensor.capture();
+        assert_eq!([captured_color(&tiles, 63, 50), captured_color(&tiles, 64, 50)], border, "{register:#04X}");
+        assert_eq!([captured_color(&tiles, 10, 50), captured_color(&tiles, 100, 50)], [2, 1]);
+    }
+}
+
+// 32 KiB ROM without an MBC and a valid header checksum
+fn rom(old_licensee_code: u8, new_licensee_code: &[u8; 2]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0134..0x0138].copy_from_slice(b"TEST");
+    rom[0x0144..0x0146].copy_from_slice(new_licensee_code);
+    rom[0x014B] = old_licensee_code;
+    rom[0x014D] = header::header_checksum(&rom);
+    rom
+}
+
+#[test]
+fn strict_mode_rejects_a_bad_header_checksum() {
+    let valid = rom(0x01, b"00");
+    assert!(Cartridge::load_with_mode(PathBuf::from("test.gb"), valid.clone(), LoadMode::Strict).is_ok());
+
+    let mut corrupt = valid;
+    corrupt[0x014D] ^= 0xFF;
+    assert_eq!(
+        Cartridge::load_with_mode(PathBuf::from("test.gb"), corrupt.clone(), LoadMode::Strict).err(),
+        Some(CartridgeError::CheckSumFailure)
+    );
+    let cartridge = Cartridge::load_with_mode(PathBuf::from("test.gb"), corrupt, LoadMode::Lenient).unwrap();
+    assert!(!cartridge.info().header_checksum_valid());
+    assert!(cartridge.info().warnings().contains(&CartridgeError::CheckSumFailure));
+}
+
+#[test]
+fn strict_mode_only_warns_about_an_unknown_rom_size() {
+    let mut unknown_size = rom(0x01, b"00");
+    unknown_size[0x0148] = 0x20;
+    unknown_size[0x014D] = header::header_checksum(&unknown_size);
+    let cartridge = Cartridge::load_with_mode(PathBuf::from("test.gb"), unknown_size, LoadMode::Strict).unwrap();
+    assert_eq!(cartridge.info().rom_size, None);
+    assert!(cartridge.info().warnings().contains(&CartridgeError::InvalidRomSize(0x20)));
+}
+
+#[test]
+fn licensees_use_the_old_or_new_code() {
+    let info = RomInfo::parse(&rom(0x01, b"00")).unwrap();
+    assert_eq!(info.licensee, Licensee::Old(0x01));
+    assert_eq!(info.licensee_name(), Some("Nintendo"));
+    assert!(info.licensed_by_nintendo());
+
+    let info = RomInfo::parse(&rom(0xA4, b"01")).unwrap();
+    assert_eq!(info.licensee_name(), Some("Konami"));
+    assert!(!info.licensed_by_nintendo());
+
+    // 0x33 defers to the two ASCII characters of the new code
+    let info = RomInfo::parse(&rom(0x33, b"A4")).unwrap();
+    assert_eq!(info.licensee, Licensee::New("A4".to_string()));
+    assert_eq!(info.licensee_name(), Some("Konami (Yu-Gi-Oh!)"));
+    let info = RomInfo::parse(&rom(0x33, b"01")).unwrap();
+    assert_eq!(info.licensee_name(), Some("Nintendo Research & Development 1"));
+    assert!(info.licensed_by_nintendo());
+
+    assert_eq!(RomInfo::parse(&rom(0x33, b"ZZ")).unwrap().licensee_name(), None);
+    assert_eq!(RomInfo::parse(&rom(0xFE, b"00")).unwrap().licensee_name(), None);
+}
+
+#[test]
+fn mbc3_saves_of_the_wrong_length_are_rejected() {
+    let mut mbc = Mbc3::new(vec![0; 0x8000], 1, true, true, true).unwrap();
+    assert_eq!(mbc.load_ram(&[0; 0x1000]), Err(CartridgeError::IncorrectLengthLoaded));
+    assert_eq!(mbc.load_ram(&[0; 0x2000 + 4]), Err(CartridgeError::IncorrectLengthLoaded));
+
+    // The 8 RTC bytes lead the save, without them only the RAM is loaded
+    let mut save = 1234u64.to_be_bytes().to_vec();
+    save.extend([0x5A; 0x2000]);
+    assert_eq!(mbc.load_ram(&save), Ok(()));
+    assert_eq!(mbc.dump_ram(), save);
+    assert_eq!(mbc.load_ram(&[0xA5; 0x2000]), Ok(()));
+    assert_eq!(mbc.dump_ram()[..8], 1234u64.to_be_bytes());
+    assert!(mbc.dump_ram()[8..].iter().all(|&byte| byte == 0xA5));
+}
+
+#[test]
+fn mbc3_without_a_clock_ignores_the_saved_time() {
+    let mut mbc = Mbc3::new(vec![0; 0x8000], 1, true, true, false).unwrap();
+    let mut save = 1234u64.to_be_bytes().to_vec();
+    save.extend([0x5A; 0x2000]);
+    assert_eq!(mbc.load_ram(&save), Ok(()));
+    assert_eq!(mbc.dump_ram()[..8], [0; 8]);
+}
@@ -5,6 +5,74 @@ pub enum GameBoyMode {
     Monochrome,
     Color,
     ColorAsMonochrome,
+    SuperGameBoy,
+}
+
+// Hardware revision being emulated
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Model {
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+    Agb,
+}
+
+impl Model {
+    pub const ALL: [Model; 6] = [Model::Dmg, Model::Mgb, Model::Sgb, Model::Sgb2, Model::Cgb, Model::Agb];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Sgb2 => "sgb2",
+            Model::Cgb => "cgb",
+            Model::Agb => "agb",
+        }
+    }
+
+    pub fn is_color(&self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    pub fn is_super_game_boy(&self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+
+    // Share of the previous frame the LCD still shows, the original DMG screen is the slowest to respond
+    pub fn lcd_persistence(&self) -> f32 {
+        match self {
+            Model::Dmg => 0.6,
+            Model::Mgb => 0.5,
+            // The SNES draws to a TV without any ghosting
+            Model::Sgb | Model::Sgb2 => 0.0,
+            Model::Cgb => 0.35,
+            Model::Agb => 0.2,
+        }
+    }
+
+    // Mode a cartridge runs in on this model, color only when the game supports it
+    pub fn mode(&self, cgb_game: bool) -> GameBoyMode {
+        match self {
+            Model::Dmg | Model::Mgb => GameBoyMode::Monochrome,
+            Model::Sgb | Model::Sgb2 => GameBoyMode::SuperGameBoy,
+            Model::Cgb | Model::Agb if cgb_game => GameBoyMode::Color,
+            Model::Cgb | Model::Agb => GameBoyMode::ColorAsMonochrome,
+        }
+    }
+}
+
+impl std::str::FromStr for Model {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Model::ALL
+            .into_iter()
+            .find(|model| model.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Unknown model `{name}`"))
+    }
 }
 
 pub trait MemoryInterface {
@@ -12,7 +80,7 @@ pub trait MemoryInterface {
 
     fn load_16(&self, address: u16) -> u16 {
         let lo = self.load_8(address) as u16;
-        let hi = self.load_8(address + 1) as u16;
+        let hi = self.load_8(address.wrapping_add(1)) as u16;
         hi << 8 | lo
     }
 
@@ -22,12 +90,32 @@ pub trait MemoryInterface {
         let hi = (value >> 8) as u8;
         let lo = (value & 0xFF) as u8;
         self.store_8(address, lo);
-        self.store_8(address + 1, hi);
+        self.store_8(address.wrapping_add(1), hi);
     }
 
     fn cycle(&mut self, cycles: u32, cpu_halted: bool) -> u32;
 
-    fn change_speed(&mut self);
+    // Returns true when an armed CGB speed switch was performed
+    fn change_speed(&mut self) -> bool;
+
+    // STOP mode freezes the system clock until a button is pressed
+    fn set_stopped(&mut self, _stopped: bool) {}
+
+    // True while a selected joypad input line is held low
+    fn joypad_active(&self) -> bool {
+        false
+    }
+
+    // CPU reads, writes and 16-bit increments and decrements of a register pointing at OAM corrupt it on
+    // monochrome models
+    fn corrupt_oam(&mut self, _address: u16, _access: OamAccess) {}
+}
+
+// How the CPU touched OAM, increments and decrements corrupt it the same way writes do
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum OamAccess {
+    Read,
+    Write,
 }
 
 pub trait SystemMemoryAccess {
@@ -0,0 +1,21 @@
+use ironboy_cartridge::CartridgeError;
+use ironboy_ppu::PaletteError;
+use thiserror::Error;
+
+use crate::{input::MacroError, movie::MovieError, rom::RomError};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GameBoyError {
+    #[error(transparent)]
+    Cartridge(#[from] CartridgeError),
+    #[error(transparent)]
+    Rom(#[from] RomError),
+    #[error(transparent)]
+    Palette(#[from] PaletteError),
+    #[error(transparent)]
+    Macro(#[from] MacroError),
+    #[error(transparent)]
+    Movie(#[from] MovieError),
+    #[error("Screenshot could not be saved: `{0}`")]
+    ScreenshotFailure(String),
+}
@@ -0,0 +1,11 @@
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GameBoyEvent {
+    // The rumble motor of an MBC5 rumble cartridge was switched on (true) or off (false)
+    Rumble(bool),
+    // An illegal opcode locked up the CPU, it stays locked until the ROM is loaded again
+    CpuLocked { opcode: u8, address: u16 },
+    // A movie played to its end, the player's buttons are back in control
+    MovieFinished,
+    // Memory differed from a RAM checkpoint of the movie being played, the first differing byte is reported
+    MovieDesync { cycle: u64, address: u16, expected: u8, actual: u8 },
+}
@@ -0,0 +1,205 @@
+use std::str::FromStr;
+
+use crate::{FrameBuffer, unpack, xrgb};
+
+// CPU side post-processing of a finished frame, the result is scaled up by `scale`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Filter {
+    // Frame left as is, the display scales it with nearest neighbour
+    Nearest,
+    // AdvMAME2x, sharpens diagonal edges without adding colors
+    Scale2x,
+    // AdvMAME3x
+    Scale3x,
+    // xBR level 1, smooths edges by blending along the detected direction
+    Xbr2x,
+    // Dark gaps between pixels like the Game Boy's LCD
+    LcdGrid,
+    // Dimmed scanlines and an RGB aperture grille like a CRT television
+    Crt,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 6] = [
+        Filter::Nearest,
+        Filter::Scale2x,
+        Filter::Scale3x,
+        Filter::Xbr2x,
+        Filter::LcdGrid,
+        Filter::Crt,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Scale2x => "scale2x",
+            Filter::Scale3x => "scale3x",
+            Filter::Xbr2x => "xbr",
+            Filter::LcdGrid => "lcd",
+            Filter::Crt => "crt",
+        }
+    }
+
+    pub fn next(&self) -> Filter {
+        let index = Filter::ALL.iter().position(|filter| filter == self).unwrap_or_default();
+        Filter::ALL[(index + 1) % Filter::ALL.len()]
+    }
+
+    pub fn scale(&self) -> usize {
+        match self {
+            Filter::Nearest => 1,
+            Filter::Scale2x | Filter::Xbr2x => 2,
+            Filter::Scale3x | Filter::LcdGrid | Filter::Crt => 3,
+        }
+    }
+
+    // Filters `frame` into `output`, which is only reallocated when the frame size changes
+    pub fn apply(&self, frame: &FrameBuffer, output: &mut FrameBuffer) {
+        let scale = self.scale();
+        let (width, height) = (frame.width(), frame.height());
+        if output.width() != width * scale || output.height() != height * scale {
+            *output = FrameBuffer::new(width * scale, height * scale, false);
+        }
+
+        let input = Pixels {
+            pixels: frame.pixels(),
+            width,
+            height,
+        };
+        let output_width = width * scale;
+        let pixels = output.pixels_mut();
+        for y in 0..height {
+            for x in 0..width {
+                let block = match self {
+                    Filter::Nearest => [input.get(x, y, 0, 0); 9],
+                    Filter::Scale2x => scale2x(&input, x, y),
+                    Filter::Scale3x => scale3x(&input, x, y),
+                    Filter::Xbr2x => xbr2x(&input, x, y),
+                    Filter::LcdGrid => lcd_grid(input.get(x, y, 0, 0)),
+                    Filter::Crt => crt(input.get(x, y, 0, 0)),
+                };
+                for row in 0..scale {
+                    let offset = (y * scale + row) * output_width + x * scale;
+                    pixels[offset..offset + scale].copy_from_slice(&block[row * scale..(row + 1) * scale]);
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        Filter::ALL
+            .into_iter()
+            .find(|filter| filter.name() == s)
+            .ok_or_else(|| format!("unknown filter {s}"))
+    }
+}
+
+struct Pixels<'a> {
+    pixels: &'a [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Pixels<'_> {
+    // Neighbour of (x, y), edges repeat the outermost pixels
+    fn get(&self, x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Output blocks are row major and padded to 3x3, 2x filters only fill the first four entries
+fn scale2x(input: &Pixels, x: usize, y: usize) -> [u32; 9] {
+    let [b, d, e, f, h] = [(0, -1), (-1, 0), (0, 0), (1, 0), (0, 1)].map(|(dx, dy)| input.get(x, y, dx, dy));
+    if b == h || d == f {
+        return [e; 9];
+    }
+
+    let mut block = [e; 9];
+    block[0] = if d == b { d } else { e };
+    block[1] = if b == f { f } else { e };
+    block[2] = if d == h { d } else { e };
+    block[3] = if h == f { f } else { e };
+    block
+}
+
+fn scale3x(input: &Pixels, x: usize, y: usize) -> [u32; 9] {
+    let [a, b, c, d, e, f, g, h, i] =
+        [(-1, -1), (0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].map(|(dx, dy)| input.get(x, y, dx, dy));
+    if b == h || d == f {
+        return [e; 9];
+    }
+
+    [
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) { b } else { e },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) { d } else { e },
+        e,
+        if (b == f && e != i) || (h == f && e != c) { f } else { e },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) { h } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+fn xbr2x(input: &Pixels, x: usize, y: usize) -> [u32; 9] {
+    let e = input.get(x, y, 0, 0);
+    let mut block = [e; 9];
+    // Each output corner looks at the neighbourhood mirrored towards it
+    for (corner, (sx, sy)) in [(0, (-1, -1)), (1, (1, -1)), (2, (-1, 1)), (3, (1, 1))] {
+        let pixel = |dx: isize, dy: isize| input.get(x, y, dx * sx, dy * sy);
+        let [b, c, d, f, g, h, i] = [(0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)].map(|(dx, dy)| pixel(dx, dy));
+        let [f4, i4, h5, i5] = [(2, 0), (2, 1), (0, 2), (1, 2)].map(|(dx, dy)| pixel(dx, dy));
+
+        // Weights of an edge running between F and H against one running through E and I
+        let across = distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4 * distance(h, f);
+        let along = distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+        if across < along {
+            let closest = if distance(e, f) <= distance(e, h) { f } else { h };
+            block[corner] = mix(e, closest, 1, 1);
+        }
+    }
+    block
+}
+
+fn lcd_grid(color: u32) -> [u32; 9] {
+    let gap = mix(color, 0, 3, 1);
+    [color, color, gap, color, color, gap, gap, gap, gap]
+}
+
+fn crt(color: u32) -> [u32; 9] {
+    let (r, g, b) = unpack(color);
+    // Every column favours one phosphor, the last row is the dark gap between scanlines
+    let dim = |channel: u8| channel / 2 + channel / 4;
+    let mask = [xrgb(r, dim(g), dim(b)), xrgb(dim(r), g, dim(b)), xrgb(dim(r), dim(g), b)];
+    std::array::from_fn(|index| match index / 3 {
+        2 => mix(mask[index % 3], 0, 1, 1),
+        _ => mask[index % 3],
+    })
+}
+
+// Weighted average of two colors
+fn mix(first: u32, second: u32, first_weight: u32, second_weight: u32) -> u32 {
+    let (first, second) = (unpack(first), unpack(second));
+    let total = first_weight + second_weight;
+    let channel = |a: u8, b: u8| ((a as u32 * first_weight + b as u32 * second_weight) / total) as u8;
+    xrgb(channel(first.0, second.0), channel(first.1, second.1), channel(first.2, second.2))
+}
+
+// Perceptual difference in YUV, the weights xBR uses
+fn distance(first: u32, second: u32) -> u32 {
+    let (r1, g1, b1) = unpack(first);
+    let (r2, g2, b2) = unpack(second);
+    let (r, g, b) = (r1 as f32 - r2 as f32, g1 as f32 - g2 as f32, b1 as f32 - b2 as f32);
+    let y = (0.299 * r + 0.587 * g + 0.114 * b).abs();
+    let u = (-0.169 * r - 0.331 * g + 0.5 * b).abs();
+    let v = (0.5 * r - 0.419 * g - 0.081 * b).abs();
+    (48.0 * y + 7.0 * u + 6.0 * v) as u32
+}
@@ -1,41 +1,221 @@
-use ironboy_cartridge::Cartridge;
-use ironboy_common::CPU_CLOCK_SPEED;
-use ironboy_cpu::{Cpu, registers::Registers};
+use std::{collections::VecDeque, path::Path};
+
+use ironboy_cartridge::{CameraImage, Cartridge, CgbSupport, LoadMode, RomInfo};
+use ironboy_common::{CPU_CLOCK_SPEED, MemoryInterface, Model};
+use ironboy_cpu::{Cpu, CpuState, registers::Registers};
+use ironboy_ppu::{ColorCorrection, CompatibilityPalette, DmgPalette, FrameBuffer, PaletteCombo};
 use ironboy_system_bus::SystemBus;
 
-use crate::{FPS, JoypadButton};
+use crate::{
+    FPS, Filter, HighPass, JoypadButton, SocdPolicy,
+    error::GameBoyError,
+    event::GameBoyEvent,
+    input::{InputMacro, Inputs, bit},
+    movie::{Movie, MovieError, MovieEvent, MovieFormat, MoviePlayback, MovieState},
+    tilt::{Tilt, TiltDirection},
+};
+
+// Oldest events are dropped when a frontend never polls
+const MAX_PENDING_EVENTS: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GameBoyOptions {
+    pub load_mode: LoadMode,
+    // Hardware to emulate, a Game Boy Color when not set
+    pub model: Option<Model>,
+    // Button combo held during the CGB boot logo, the title checksum picks the palette when not set
+    pub palette_combo: Option<PaletteCombo>,
+}
+
+impl Default for GameBoyOptions {
+    fn default() -> Self {
+        GameBoyOptions {
+            load_mode: LoadMode::Strict,
+            model: None,
+            palette_combo: None,
+        }
+    }
+}
 
 pub struct GameBoy {
     pub cpu: Cpu<SystemBus>,
+    model: Model,
     game_title: String,
     pub volume: u8,
+    tilt: Tilt,
+    inputs: Inputs,
+    // Buttons currently pressed on the joypad, as a mask of `bit`
+    joypad_state: u8,
+    movie: Option<MovieState>,
+    rom_crc32: u32,
+    events: VecDeque<GameBoyEvent>,
+    rumble_active: bool,
+    cpu_locked: bool,
 }
 
 impl GameBoy {
-    pub fn new(rom_name: &str, buffer: Vec<u8>) -> GameBoy {
-        let cartridge = Cartridge::load(rom_name.into(), buffer).unwrap();
+    pub fn new(rom_name: &str, buffer: Vec<u8>) -> Result<GameBoy, GameBoyError> {
+        Self::with_options(rom_name, buffer, GameBoyOptions::default())
+    }
+
+    pub fn with_options(rom_name: &str, buffer: Vec<u8>, options: GameBoyOptions) -> Result<GameBoy, GameBoyError> {
+        let rom_crc32 = crc32fast::hash(&buffer);
+        let cartridge = Cartridge::load_with_mode(rom_name.into(), buffer, options.load_mode)?;
         let game_title = cartridge.title().to_string();
-        let mode = cartridge.mode();
-        GameBoy {
-            cpu: Cpu::new(SystemBus::new(cartridge), Registers::new(mode)),
+        let model = options.model.unwrap_or(Model::Cgb);
+        let mode = model.mode(cartridge.info().cgb_support != CgbSupport::None);
+        let palette = match options.palette_combo {
+            Some(combo) => combo.palette(),
+            None => CompatibilityPalette::for_title(&cartridge.info().raw_title, cartridge.info().licensed_by_nintendo()),
+        };
+        let mut bus = SystemBus::new(cartridge, mode, model);
+        bus.ppu.set_compatibility_palette(&palette);
+        Ok(GameBoy {
+            cpu: Cpu::new(bus, Registers::with_model(model, mode)),
+            model,
             game_title,
             volume: 50,
-        }
+            tilt: Tilt::new(),
+            inputs: Inputs::new(),
+            joypad_state: 0,
+            movie: None,
+            rom_crc32,
+            events: VecDeque::new(),
+            rumble_active: false,
+            cpu_locked: false,
+        })
     }
 
-    pub fn run(&mut self) -> Vec<Vec<(u8, u8, u8)>> {
-        let mut frames = Vec::new();
+    // Runs for one frame's worth of cycles, true when the PPU completed a frame that `frame` now returns
+    pub fn run(&mut self) -> bool {
+        let mut frame_completed = false;
         let cycles_per_frame = CPU_CLOCK_SPEED as f32 / FPS;
         let mut cycles_passed = 0.0;
         while cycles_passed <= cycles_per_frame {
-            let cycles = self.cpu.cycle();
-            if self.ppu_updated() {
-                let frame = self.cpu.bus.ppu.screen_buffer.clone();
-                frames.push(frame);
+            let elapsed_cycles = self.cycles();
+            if let Some(MovieState::Playing(playback)) = &mut self.movie
+                && playback.advance(elapsed_cycles)
+            {
+                self.apply_inputs();
             }
+            self.check_movie_ram();
+            let cycles = self.cpu.cycle();
+            self.update_rumble();
+            self.update_cpu_locked();
+            frame_completed |= self.ppu_updated();
             cycles_passed += (cycles) as f32;
         }
-        frames
+        self.inputs.next_frame();
+        self.apply_inputs();
+        self.update_movie();
+        frame_completed
+    }
+
+    // Brings the joypad to the buttons of the movie being played, or of the player with turbo and macros otherwise
+    fn apply_inputs(&mut self) {
+        let elapsed_cycles = self.cycles();
+        let pressed = match &self.movie {
+            Some(MovieState::Playing(playback)) => playback.pressed(),
+            _ => self.inputs.pressed(),
+        };
+        for button in JoypadButton::ALL {
+            let down = pressed & bit(button) != 0;
+            if down == (self.joypad_state & bit(button) != 0) {
+                continue;
+            }
+            match down {
+                true => self.cpu.bus.joy_pad.button_down(button),
+                false => self.cpu.bus.joy_pad.button_up(button),
+            }
+            if let Some(MovieState::Recording(movie)) = &mut self.movie {
+                movie.events.push(MovieEvent {
+                    cycle: elapsed_cycles,
+                    button,
+                    pressed: down,
+                });
+            }
+        }
+        self.joypad_state = pressed;
+    }
+
+    // Compares memory with the checkpoints of the movie being played
+    fn check_movie_ram(&mut self) {
+        let elapsed_cycles = self.cycles();
+        while let Some(MovieState::Playing(playback)) = &mut self.movie
+            && let Some(checkpoint) = playback.checkpoint_due(elapsed_cycles)
+        {
+            let mismatch = checkpoint.expected.iter().enumerate().find_map(|(offset, &expected)| {
+                let address = checkpoint.address.wrapping_add(offset as u16);
+                let actual = self.cpu.load_8(address);
+                (actual != expected).then_some((address, expected, actual))
+            });
+            if let Some((address, expected, actual)) = mismatch {
+                self.push_event(GameBoyEvent::MovieDesync {
+                    cycle: checkpoint.cycle,
+                    address,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    fn update_movie(&mut self) {
+        if let Some(MovieState::Playing(playback)) = &self.movie
+            && playback.finished(self.cycles())
+        {
+            self.movie = None;
+            self.apply_inputs();
+            self.push_event(GameBoyEvent::MovieFinished);
+        }
+    }
+
+    fn update_rumble(&mut self) {
+        let rumble_active = self.cpu.bus.rumble_active();
+        if rumble_active != self.rumble_active {
+            self.rumble_active = rumble_active;
+            self.push_event(GameBoyEvent::Rumble(rumble_active));
+        }
+    }
+
+    fn update_cpu_locked(&mut self) {
+        if self.cpu_locked {
+            return;
+        }
+        if let Some(illegal) = self.cpu.locked() {
+            self.cpu_locked = true;
+            self.push_event(GameBoyEvent::CpuLocked {
+                opcode: illegal.opcode,
+                address: illegal.address,
+            });
+        }
+    }
+
+    fn push_event(&mut self, event: GameBoyEvent) {
+        if self.events.len() >= MAX_PENDING_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn poll_event(&mut self) -> Option<GameBoyEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn rumble_active(&self) -> bool {
+        self.rumble_active
+    }
+
+    pub fn cpu_state(&self) -> CpuState {
+        self.cpu.state()
+    }
+
+    pub fn cpu_registers(&self) -> &Registers {
+        self.cpu.registers()
+    }
+
+    pub fn set_trace_logging(&mut self, enabled: bool) {
+        self.cpu.set_debugging(enabled);
     }
 
     fn ppu_updated(&mut self) -> bool {
@@ -44,14 +224,89 @@ impl GameBoy {
         result
     }
 
-    pub fn ppu_buffer(&self) -> &[(u8, u8, u8)] {
-        &self.cpu.bus.ppu.screen_buffer
+    // Latest completed frame, borrowed from the PPU until the next call to `run`
+    pub fn frame(&self) -> &FrameBuffer {
+        self.cpu.bus.ppu.frame()
+    }
+
+    // Writes the latest frame as a PNG, scaled up by the filter
+    pub fn save_screenshot(&self, path: &Path, filter: Filter) -> Result<(), GameBoyError> {
+        let mut filtered = FrameBuffer::new(0, 0, false);
+        filter.apply(self.frame(), &mut filtered);
+        image::save_buffer(
+            path,
+            &filtered.to_rgb(),
+            filtered.width() as u32,
+            filtered.height() as u32,
+            image::ExtendedColorType::Rgb8,
+        )
+        .map_err(|error| GameBoyError::ScreenshotFailure(error.to_string()))
+    }
+
+    pub fn set_index_buffer(&mut self, enabled: bool) {
+        self.cpu.bus.ppu.set_index_buffer(enabled);
+    }
+
+    // 160x144, or 256x224 with the SGB border
+    pub fn screen_size(&self) -> (usize, usize) {
+        self.cpu.bus.ppu.frame_size()
     }
 
     pub fn game_title(&self) -> String {
         self.game_title.clone()
     }
 
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    // Recolors a DMG game running on a Game Boy Color, has no effect otherwise
+    pub fn set_compatibility_palette(&mut self, combo: PaletteCombo) {
+        self.cpu.bus.ppu.set_compatibility_palette(&combo.palette());
+    }
+
+    // Colors of the BG, OBJ0 and OBJ1 shades on monochrome models
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.cpu.bus.ppu.set_dmg_palette(palette);
+    }
+
+    pub fn dmg_palette(&self) -> &DmgPalette {
+        self.cpu.bus.ppu.dmg_palette()
+    }
+
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.cpu.bus.ppu.set_color_correction(correction);
+    }
+
+    pub fn color_correction(&self) -> ColorCorrection {
+        self.cpu.bus.ppu.color_correction()
+    }
+
+    // Ghosting of the emulated model's LCD, needed by games that flicker sprites for transparency
+    pub fn set_frame_blending(&mut self, enabled: bool) {
+        let persistence = enabled.then(|| self.model.lcd_persistence());
+        self.cpu.bus.ppu.set_frame_blending(persistence);
+    }
+
+    // Custom LCD response, the share of the previous frame kept in each new one
+    pub fn set_lcd_persistence(&mut self, persistence: f32) {
+        self.cpu.bus.ppu.set_frame_blending(Some(persistence));
+    }
+
+    pub fn frame_blending(&self) -> bool {
+        self.cpu.bus.ppu.frame_blending().is_some()
+    }
+
+    pub fn load_dmg_palette(&mut self, path: &Path) -> Result<(), GameBoyError> {
+        let palette = DmgPalette::from_file(path)?;
+        self.set_dmg_palette(palette);
+        Ok(())
+    }
+
+    pub fn rom_info(&self) -> &RomInfo {
+        self.cpu.bus.rom_info()
+    }
+
     pub fn increase_volume(&mut self) {
         if self.volume > 95 {
             return;
@@ -66,11 +321,234 @@ impl GameBoy {
         self.volume -= 5;
     }
 
+    // Output rate of the band-limited audio, in samples per second and channel
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.cpu.bus.apu.set_sample_rate(sample_rate);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.cpu.bus.apu.sample_rate()
+    }
+
+    pub fn set_high_pass(&mut self, high_pass: HighPass) {
+        self.cpu.bus.apu.set_high_pass(high_pass);
+    }
+
+    pub fn high_pass(&self) -> HighPass {
+        self.cpu.bus.apu.high_pass()
+    }
+
+    // Interleaved stereo samples produced so far, before `volume` is applied
+    pub fn read_audio_f32(&mut self, samples: &mut [f32]) -> usize {
+        self.cpu.bus.apu.read_samples_f32(samples)
+    }
+
+    pub fn read_audio_i16(&mut self, samples: &mut [i16]) -> usize {
+        self.cpu.bus.apu.read_samples_i16(samples)
+    }
+
     pub fn button_up(&mut self, button: JoypadButton) {
-        self.cpu.bus.joy_pad.button_up(button)
+        self.inputs.set_held(button, false);
+        self.apply_inputs();
     }
 
     pub fn button_down(&mut self, button: JoypadButton) {
-        self.cpu.bus.joy_pad.button_down(button)
+        self.inputs.set_held(button, true);
+        self.apply_inputs();
+    }
+
+    // Records every joypad change from power-on until `stop_movie_recording`
+    pub fn start_movie_recording(&mut self) -> Result<(), GameBoyError> {
+        if self.cycles() != 0 {
+            return Err(MovieError::NotAtPowerOn.into());
+        }
+        let movie = Movie::new(self.model, self.socd_policy(), self.rom_crc32);
+        self.movie = Some(MovieState::Recording(movie));
+        Ok(())
+    }
+
+    pub fn stop_movie_recording(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieState::Recording(mut movie)) => {
+                movie.length = self.cycles();
+                Some(movie)
+            }
+            other => {
+                self.movie = other;
+                None
+            }
+        }
+    }
+
+    // Replays a movie from power-on, the player's buttons are ignored until it finishes with GameBoyEvent::MovieFinished
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), GameBoyError> {
+        if self.cycles() != 0 {
+            return Err(MovieError::NotAtPowerOn.into());
+        }
+        if movie.rom_crc32 != self.rom_crc32 {
+            return Err(MovieError::WrongRom {
+                expected: movie.rom_crc32,
+                actual: self.rom_crc32,
+            }
+            .into());
+        }
+        if movie.model != self.model {
+            return Err(MovieError::WrongModel {
+                expected: movie.model.name().to_string(),
+                actual: self.model.name().to_string(),
+            }
+            .into());
+        }
+        self.set_socd_policy(movie.socd);
+        self.movie = Some(MovieState::Playing(MoviePlayback::new(movie)));
+        self.apply_inputs();
+        Ok(())
+    }
+
+    // Reads a movie of ours, BizHawk or VBA, imported ones are checked against the loaded ROM where the format allows
+    pub fn load_movie(&self, path: &Path) -> Result<Movie, GameBoyError> {
+        let data = std::fs::read(path).map_err(|error| MovieError::ReadFailure(error.to_string()))?;
+        if MovieFormat::detect(&data) == Some(MovieFormat::IronBoy) {
+            return Ok(Movie::parse(&String::from_utf8_lossy(&data))?);
+        }
+
+        let imported = Movie::import(&data)?;
+        let info = self.rom_info();
+        if let Some((header, global)) = imported.checksums
+            && (header, global) != (info.header_checksum, info.global_checksum)
+        {
+            return Err(MovieError::WrongChecksums {
+                expected_header: header,
+                expected_global: global,
+                actual_header: info.header_checksum,
+                actual_global: info.global_checksum,
+            }
+            .into());
+        }
+        Ok(Movie::from_frames(imported.model, self.rom_crc32, &imported.frames))
+    }
+
+    pub fn play_movie_file(&mut self, path: &Path) -> Result<(), GameBoyError> {
+        let movie = self.load_movie(path)?;
+        self.play_movie(movie)
+    }
+
+    pub fn stop_movie(&mut self) {
+        if let Some(MovieState::Playing(_)) = self.movie {
+            self.movie = None;
+            self.apply_inputs();
+        }
+    }
+
+    pub fn movie_playing(&self) -> bool {
+        matches!(self.movie, Some(MovieState::Playing(_)))
+    }
+
+    pub fn movie_recording(&self) -> bool {
+        matches!(self.movie, Some(MovieState::Recording(_)))
+    }
+
+    // Cycles of the 4 MHz clock since power-on, the clock movies are timed with. Double speed runs twice the CPU cycles in the same time
+    pub fn cycles(&self) -> u64 {
+        self.cpu.bus.elapsed_cycles()
+    }
+
+    // How opposing directions held together reach the game
+    pub fn set_socd_policy(&mut self, policy: SocdPolicy) {
+        self.cpu.bus.joy_pad.set_socd_policy(policy);
+    }
+
+    pub fn socd_policy(&self) -> SocdPolicy {
+        self.cpu.bus.joy_pad.socd_policy()
+    }
+
+    // Autofire, the button is pressed and released at the turbo rate while held
+    pub fn turbo_down(&mut self, button: JoypadButton) {
+        self.inputs.set_turbo(button, true);
+        self.apply_inputs();
+    }
+
+    pub fn turbo_up(&mut self, button: JoypadButton) {
+        self.inputs.set_turbo(button, false);
+        self.apply_inputs();
+    }
+
+    // Presses per second of turbo buttons
+    pub fn set_turbo_rate(&mut self, presses_per_second: u32) {
+        self.inputs.set_turbo_rate(presses_per_second);
+    }
+
+    pub fn turbo_rate(&self) -> u32 {
+        self.inputs.turbo_rate()
+    }
+
+    // Replays a macro from its first step on top of the buttons the player holds, replacing one already playing
+    pub fn play_macro(&mut self, input_macro: InputMacro) {
+        self.inputs.play(input_macro);
+        self.apply_inputs();
+    }
+
+    pub fn play_macro_file(&mut self, path: &Path) -> Result<(), GameBoyError> {
+        let input_macro = InputMacro::from_file(path)?;
+        self.play_macro(input_macro);
+        Ok(())
+    }
+
+    pub fn stop_macro(&mut self) {
+        self.inputs.stop_playback();
+        self.apply_inputs();
+    }
+
+    pub fn macro_playing(&self) -> bool {
+        self.inputs.playing()
+    }
+
+    // Records the joypad state of every following frame until `stop_recording_macro`
+    pub fn start_recording_macro(&mut self) {
+        self.inputs.start_recording();
+    }
+
+    pub fn stop_recording_macro(&mut self) -> Option<InputMacro> {
+        self.inputs.stop_recording()
+    }
+
+    pub fn recording_macro(&self) -> bool {
+        self.inputs.recording()
+    }
+
+    pub fn tilt_key_down(&mut self, direction: TiltDirection) {
+        self.tilt.key_down(direction);
+        self.update_tilt();
+    }
+
+    pub fn tilt_key_up(&mut self, direction: TiltDirection) {
+        self.tilt.key_up(direction);
+        self.update_tilt();
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt.set_analog(x, y);
+        self.update_tilt();
+    }
+
+    pub fn set_tilt_from_pointer(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.tilt.set_pointer(x, y, width, height);
+        self.update_tilt();
+    }
+
+    fn update_tilt(&mut self) {
+        let (x, y) = self.tilt.value();
+        self.cpu.bus.set_tilt(x, y);
+    }
+
+    // Image picked up by the Pocket Camera sensor, falls back to a test pattern until set
+    pub fn set_camera_image(&mut self, image: CameraImage) {
+        self.cpu.bus.set_camera_image(image);
+    }
+
+    pub fn load_camera_image(&mut self, path: &Path) -> Result<(), GameBoyError> {
+        let image = CameraImage::from_file(path)?;
+        self.set_camera_image(image);
+        Ok(())
     }
 }
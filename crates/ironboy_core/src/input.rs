@@ -0,0 +1,238 @@
+use std::{fmt, path::Path};
+
+use thiserror::Error;
+
+use crate::{FPS, JoypadButton};
+
+// Presses per second of a turbo button unless set otherwise
+pub const DEFAULT_TURBO_RATE: u32 = 10;
+
+// Bit of a button in the masks below, in JoypadButton::ALL order
+pub(crate) fn bit(button: JoypadButton) -> u8 {
+    let index = JoypadButton::ALL.iter().position(|&other| other == button).unwrap_or_default();
+    1 << index
+}
+
+fn buttons(mask: u8) -> Vec<JoypadButton> {
+    JoypadButton::ALL.into_iter().filter(|&button| mask & bit(button) != 0).collect()
+}
+
+// Buttons held together for a number of frames, no buttons is a pause
+#[derive(Debug, PartialEq, Clone)]
+pub struct MacroStep {
+    pub buttons: Vec<JoypadButton>,
+    pub frames: u32,
+}
+
+// Recorded sequence of joypad states, replayed one step after another
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct InputMacro {
+    steps: Vec<MacroStep>,
+}
+
+impl InputMacro {
+    pub fn new(steps: Vec<MacroStep>) -> Self {
+        InputMacro { steps }
+    }
+
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.steps.iter().map(|step| step.frames).sum()
+    }
+
+    // Lines of a frame count followed by the buttons joined with `+`, or `-` for none. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<Self, MacroError> {
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (frames, pressed) = line.split_once(char::is_whitespace).ok_or(MacroError::InvalidLine(index + 1))?;
+            let frames = frames.parse::<u32>().map_err(|_| MacroError::InvalidLine(index + 1))?;
+            let buttons = match pressed.trim() {
+                "-" => Vec::new(),
+                pressed => pressed
+                    .split('+')
+                    .map(|name| {
+                        name.trim()
+                            .parse::<JoypadButton>()
+                            .map_err(|_| MacroError::UnknownButton(name.trim().to_string()))
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+            steps.push(MacroStep { buttons, frames });
+        }
+        Ok(InputMacro { steps })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, MacroError> {
+        let text = std::fs::read_to_string(path).map_err(|error| MacroError::ReadFailure(error.to_string()))?;
+        InputMacro::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MacroError> {
+        std::fs::write(path, self.to_string()).map_err(|error| MacroError::WriteFailure(error.to_string()))
+    }
+}
+
+impl fmt::Display for InputMacro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let names: Vec<&str> = step.buttons.iter().map(JoypadButton::name).collect();
+            match names.is_empty() {
+                true => writeln!(f, "{} -", step.frames)?,
+                false => writeln!(f, "{} {}", step.frames, names.join("+"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MacroError {
+    #[error("Macro file could not be read: `{0}`")]
+    ReadFailure(String),
+    #[error("Macro file could not be written: `{0}`")]
+    WriteFailure(String),
+    #[error("Macro line {0} needs a frame count and buttons joined with `+` or `-`")]
+    InvalidLine(usize),
+    #[error("Unknown button `{0}`")]
+    UnknownButton(String),
+}
+
+struct Playback {
+    input_macro: InputMacro,
+    step: usize,
+    frame: u32,
+}
+
+impl Playback {
+    // Moves past steps whose frames have all been played, false once the macro is done
+    fn skip_finished_steps(&mut self) -> bool {
+        while self.input_macro.steps.get(self.step).is_some_and(|step| self.frame >= step.frames) {
+            self.step += 1;
+            self.frame = 0;
+        }
+        self.step < self.input_macro.steps.len()
+    }
+}
+
+// Combines the buttons the player holds with turbo buttons and macros into the joypad state of each frame
+pub(crate) struct Inputs {
+    held: u8,
+    turbo: u8,
+    turbo_rate: u32,
+    turbo_frame: u32,
+    playback: Option<Playback>,
+    recording: Option<Vec<MacroStep>>,
+}
+
+impl Inputs {
+    pub fn new() -> Self {
+        Inputs {
+            held: 0,
+            turbo: 0,
+            turbo_rate: DEFAULT_TURBO_RATE,
+            turbo_frame: 0,
+            playback: None,
+            recording: None,
+        }
+    }
+
+    pub fn set_held(&mut self, button: JoypadButton, held: bool) {
+        match held {
+            true => self.held |= bit(button),
+            false => self.held &= !bit(button),
+        }
+    }
+
+    pub fn set_turbo(&mut self, button: JoypadButton, held: bool) {
+        // Restarting the cycle makes the first press immediate
+        if self.turbo == 0 {
+            self.turbo_frame = 0;
+        }
+        match held {
+            true => self.turbo |= bit(button),
+            false => self.turbo &= !bit(button),
+        }
+    }
+
+    pub fn set_turbo_rate(&mut self, presses_per_second: u32) {
+        self.turbo_rate = presses_per_second.max(1);
+    }
+
+    pub fn turbo_rate(&self) -> u32 {
+        self.turbo_rate
+    }
+
+    // Frames of one press and release, pressed for the first half
+    fn turbo_period(&self) -> u32 {
+        ((FPS / self.turbo_rate as f32).round() as u32).max(2)
+    }
+
+    pub fn play(&mut self, input_macro: InputMacro) {
+        let mut playback = Playback {
+            input_macro,
+            step: 0,
+            frame: 0,
+        };
+        self.playback = playback.skip_finished_steps().then_some(playback);
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputMacro> {
+        self.recording.take().map(InputMacro::new)
+    }
+
+    pub fn recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Buttons the game sees this frame
+    pub fn pressed(&self) -> u8 {
+        let mut pressed = self.held;
+        if self.turbo_frame % self.turbo_period() < self.turbo_period() / 2 {
+            pressed |= self.turbo;
+        }
+        if let Some(step) = self.playback.as_ref().and_then(|playback| playback.input_macro.steps.get(playback.step)) {
+            pressed |= step.buttons.iter().fold(0, |mask, &button| mask | bit(button));
+        }
+        pressed
+    }
+
+    // Called once per emulated frame, after it ran with `pressed`
+    pub fn next_frame(&mut self) {
+        let pressed = self.pressed();
+        if let Some(steps) = &mut self.recording {
+            let buttons = buttons(pressed);
+            match steps.last_mut() {
+                Some(step) if step.buttons == buttons => step.frames += 1,
+                _ => steps.push(MacroStep { buttons, frames: 1 }),
+            }
+        }
+
+        self.turbo_frame = (self.turbo_frame + 1) % self.turbo_period();
+        if let Some(playback) = &mut self.playback {
+            playback.frame += 1;
+            if !playback.skip_finished_steps() {
+                self.playback = None;
+            }
+        }
+    }
+}
@@ -1,5 +1,27 @@
+pub mod error;
+pub mod event;
+pub mod filter;
 pub mod gb;
+pub mod input;
+pub mod movie;
+pub mod rom;
+pub mod tilt;
 
-pub use ironboy_apu::{AUDIO_BUFFER_THRESHOLD, SAMPLING_FREQUENCY, SAMPLING_RATE};
-pub use ironboy_joypad::JoypadButton;
-pub use ironboy_ppu::{FPS, VIEWPORT_HEIGHT, VIEWPORT_WIDTH};
+#[cfg(test)]
+mod tests;
+
+pub use error::GameBoyError;
+pub use event::GameBoyEvent;
+pub use filter::Filter;
+pub use input::{InputMacro, MacroError, MacroStep};
+pub use movie::{FIRST_FRAME_CYCLES, FRAME_CYCLES, ImportedMovie, Movie, MovieError, MovieEvent, MovieFormat, RamCheckpoint};
+pub use ironboy_apu::{AUDIO_BUFFER_THRESHOLD, HighPass, SAMPLING_FREQUENCY, SAMPLING_RATE};
+pub use ironboy_cartridge::{CameraImage, CartridgeError, CgbSupport, Licensee, LoadMode, RomInfo};
+pub use ironboy_common::Model;
+pub use ironboy_cpu::{CpuState, IllegalOpcode, registers::Registers};
+pub use ironboy_joypad::{JoypadButton, SocdPolicy};
+pub use ironboy_ppu::{
+    ColorCorrection, CompatibilityPalette, DmgPalette, DmgPalettePreset, FPS, FrameBuffer, PaletteCombo, PaletteError, SGB_SCREEN_HEIGHT,
+    SGB_SCREEN_WIDTH, VIEWPORT_HEIGHT, VIEWPORT_WIDTH, unpack, xrgb,
+};
+pub use tilt::TiltDirection;
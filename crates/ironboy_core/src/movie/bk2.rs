@@ -0,0 +1,86 @@
+use std::io::{Cursor, Read};
+
+use ironboy_common::Model;
+use zip::ZipArchive;
+
+use super::{ImportedMovie, MovieError};
+use crate::{JoypadButton, input::bit};
+
+const HEADER: &str = "Header.txt";
+const INPUT_LOG: &str = "Input Log.txt";
+
+// BizHawk stores the header as `Key Value` lines and one `|...|` line of button mnemonics per frame,
+// in the order of the input log's `LogKey` line. A `.` is a released button.
+pub fn parse(data: &[u8]) -> Result<ImportedMovie, MovieError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|error| MovieError::ReadFailure(error.to_string()))?;
+    let header = read_text(&mut archive, HEADER)?;
+    let input_log = read_text(&mut archive, INPUT_LOG)?;
+
+    let value = |key: &str| {
+        header
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix(' ').map(str::trim))
+    };
+    if value("StartsFromSavestate").is_some_and(is_true) || value("StartsFromSaveRam").is_some_and(is_true) {
+        return Err(MovieError::Unsupported("a save state or save data to start from".to_string()));
+    }
+    let model = match value("Platform") {
+        Some("SGB") => Model::Sgb,
+        Some("GBC") => Model::Cgb,
+        Some("GB") | None if value("IsCGBMode").is_some_and(is_true) => Model::Cgb,
+        Some("GB") | None => Model::Dmg,
+        Some(platform) => return Err(MovieError::Unsupported(format!("the {platform} platform"))),
+    };
+
+    let mut buttons = None;
+    let mut frames = Vec::new();
+    for (index, line) in input_log.lines().enumerate() {
+        let line = line.trim();
+        if let Some(key) = line.strip_prefix("LogKey:") {
+            let names = key.split(['#', '|']).filter(|name| !name.is_empty());
+            buttons = Some(names.map(|name| name.trim_start_matches("P1 ").to_string()).collect::<Vec<_>>());
+            continue;
+        }
+        if !line.starts_with('|') {
+            continue;
+        }
+
+        let names = buttons.as_ref().ok_or(MovieError::InvalidHeader)?;
+        let states: Vec<char> = line.chars().filter(|&state| state != '|').collect();
+        if states.len() != names.len() {
+            return Err(MovieError::InvalidLine(index + 1));
+        }
+        let mut pressed = 0;
+        for (name, state) in names.iter().zip(states) {
+            if state == '.' || state == ' ' {
+                continue;
+            }
+            match name.parse::<JoypadButton>() {
+                Ok(button) => pressed |= bit(button),
+                // The console is powered on before the first frame anyway
+                Err(_) if name == "Power" && frames.is_empty() => {}
+                Err(_) if name == "Power" => return Err(MovieError::Unsupported("a power cycle".to_string())),
+                Err(_) => return Err(MovieError::UnknownButton(name.clone())),
+            }
+        }
+        frames.push(pressed);
+    }
+
+    Ok(ImportedMovie {
+        model,
+        frames,
+        checksums: None,
+    })
+}
+
+fn read_text(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String, MovieError> {
+    let mut file = archive.by_name(name).map_err(|_| MovieError::InvalidHeader)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)
+        .map_err(|error| MovieError::ReadFailure(error.to_string()))?;
+    Ok(text)
+}
+
+fn is_true(value: &str) -> bool {
+    value.eq_ignore_ascii_case("true") || value == "1"
+}
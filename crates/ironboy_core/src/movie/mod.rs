@@ -0,0 +1,358 @@
+use std::{fmt, path::Path};
+
+use ironboy_common::Model;
+use thiserror::Error;
+
+use crate::{JoypadButton, SocdPolicy, input::bit};
+
+mod bk2;
+mod vbm;
+
+const MAGIC: &str = "iron-boy movie 1";
+const ZIP_MAGIC: &[u8] = &[0x50, 0x4B, 0x03, 0x04];
+const VBM_MAGIC: &[u8] = b"VBM\x1A";
+
+// One LCD frame, the unit the input of imported movies and RAM checkpoints are timed in. It is counted on `GameBoy::cycles`,
+// which double speed doesn't run faster, so frames stay aligned with the LCD in double-speed games too
+pub const FRAME_CYCLES: u64 = 70224;
+// Frames end at VBlank like in the emulators movies are imported from. The PPU starts 32 cycles into line 0 after the boot
+// ROM, so the first frame is cut short at line 144
+pub const FIRST_FRAME_CYCLES: u64 = 144 * 456 - 32;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MovieFormat {
+    // Our own text format
+    IronBoy,
+    // BizHawk movie, a zip archive with a header and an input log
+    Bk2,
+    // VBA-rerecording movie
+    Vbm,
+}
+
+impl MovieFormat {
+    // Detected from the file contents like ROM archives
+    pub fn detect(data: &[u8]) -> Option<MovieFormat> {
+        if data.starts_with(MAGIC.as_bytes()) {
+            Some(MovieFormat::IronBoy)
+        } else if data.starts_with(ZIP_MAGIC) {
+            Some(MovieFormat::Bk2)
+        } else if data.starts_with(VBM_MAGIC) {
+            Some(MovieFormat::Vbm)
+        } else {
+            None
+        }
+    }
+}
+
+// Bytes expected in memory once the movie reached `cycle`, a mismatch means the replay desynced
+#[derive(Debug, PartialEq, Clone)]
+pub struct RamCheckpoint {
+    pub cycle: u64,
+    pub address: u16,
+    pub expected: Vec<u8>,
+}
+
+// Movie of another emulator before it is tied to the loaded ROM
+pub struct ImportedMovie {
+    pub model: Model,
+    // Joypad state of every frame as a mask of `bit`
+    pub frames: Vec<u8>,
+    // Header and global checksum of the ROM when the format records them
+    pub checksums: Option<(u8, u16)>,
+}
+
+// A button pressed or released at a cycle of the 4 MHz clock counted from power-on
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MovieEvent {
+    pub cycle: u64,
+    pub button: JoypadButton,
+    pub pressed: bool,
+}
+
+// Every joypad change of a session from power-on, replaying it on the same ROM and model reproduces the session exactly.
+// Inputs from outside the joypad (tilt, camera, the RTC's wall clock) are not part of it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Movie {
+    pub model: Model,
+    pub socd: SocdPolicy,
+    // CRC32 of the ROM the movie was recorded on, after patches
+    pub rom_crc32: u32,
+    // Cycles from power-on until recording stopped
+    pub length: u64,
+    pub events: Vec<MovieEvent>,
+    pub checkpoints: Vec<RamCheckpoint>,
+}
+
+impl Movie {
+    pub fn new(model: Model, socd: SocdPolicy, rom_crc32: u32) -> Self {
+        Movie {
+            model,
+            socd,
+            rom_crc32,
+            length: 0,
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    // Cycle a frame starts at, counted from power-on
+    pub fn frame_start(frame: u64) -> u64 {
+        match frame {
+            0 => 0,
+            frame => FIRST_FRAME_CYCLES + (frame - 1) * FRAME_CYCLES,
+        }
+    }
+
+    // Frame a cycle falls in
+    pub fn frame_at(cycle: u64) -> u64 {
+        match cycle.checked_sub(FIRST_FRAME_CYCLES) {
+            Some(cycles) => 1 + cycles / FRAME_CYCLES,
+            None => 0,
+        }
+    }
+
+    // Movie that holds the buttons of each frame until the VBlank ending it
+    pub fn from_frames(model: Model, rom_crc32: u32, frames: &[u8]) -> Self {
+        let mut movie = Movie::new(model, SocdPolicy::Allow, rom_crc32);
+        let mut pressed = 0;
+        for (frame, &buttons) in frames.iter().enumerate() {
+            for button in JoypadButton::ALL {
+                let down = buttons & bit(button) != 0;
+                if down != (pressed & bit(button) != 0) {
+                    movie.events.push(MovieEvent {
+                        cycle: Movie::frame_start(frame as u64),
+                        button,
+                        pressed: down,
+                    });
+                }
+            }
+            pressed = buttons;
+        }
+        movie.length = Movie::frame_start(frames.len() as u64);
+        movie
+    }
+
+    // Reads a BizHawk or VBA movie, the ROM it was made for is checked by the caller
+    pub fn import(data: &[u8]) -> Result<ImportedMovie, MovieError> {
+        match MovieFormat::detect(data) {
+            Some(MovieFormat::Bk2) => bk2::parse(data),
+            Some(MovieFormat::Vbm) => vbm::parse(data),
+            Some(MovieFormat::IronBoy) | None => Err(MovieError::UnknownFormat),
+        }
+    }
+
+    // Lines of a frame number, a hex address and the hex bytes expected there, lines starting with `#` are comments
+    pub fn parse_checkpoints(&mut self, text: &str) -> Result<(), MovieError> {
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [frame, address, bytes] = fields[..] else {
+                return Err(MovieError::InvalidLine(index + 1));
+            };
+            let frame = frame.parse::<u64>().map_err(|_| MovieError::InvalidLine(index + 1))?;
+            let checkpoint = parse_checkpoint(Movie::frame_start(frame), address, bytes).ok_or(MovieError::InvalidLine(index + 1))?;
+            self.checkpoints.push(checkpoint);
+        }
+        self.checkpoints.sort_by_key(|checkpoint| checkpoint.cycle);
+        Ok(())
+    }
+
+    pub fn load_checkpoints(&mut self, path: &Path) -> Result<(), MovieError> {
+        let text = std::fs::read_to_string(path).map_err(|error| MovieError::ReadFailure(error.to_string()))?;
+        self.parse_checkpoints(&text)
+    }
+
+    // A header of `model`, `socd`, `rom` (CRC32 in hex) and `length` lines, then one `<cycle> <button> <down|up>` line per event
+    // and `check <cycle> <address> <bytes>` lines for RAM checkpoints
+    pub fn parse(text: &str) -> Result<Self, MovieError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+        if lines.next().map(|(_, line)| line) != Some(MAGIC) {
+            return Err(MovieError::InvalidHeader);
+        }
+
+        let mut model = None;
+        let mut socd = None;
+        let mut rom_crc32 = None;
+        let mut length = None;
+        let mut events = Vec::new();
+        let mut checkpoints = Vec::new();
+        for (number, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                ["model", name] => model = Some(name.parse::<Model>().map_err(|_| MovieError::InvalidLine(number))?),
+                ["socd", name] => socd = Some(name.parse::<SocdPolicy>().map_err(|_| MovieError::InvalidLine(number))?),
+                ["rom", crc] => rom_crc32 = Some(u32::from_str_radix(crc, 16).map_err(|_| MovieError::InvalidLine(number))?),
+                ["check", cycle, address, bytes] => {
+                    let cycle = cycle.parse::<u64>().map_err(|_| MovieError::InvalidLine(number))?;
+                    checkpoints.push(parse_checkpoint(cycle, address, bytes).ok_or(MovieError::InvalidLine(number))?);
+                }
+                ["length", cycles] => length = Some(cycles.parse::<u64>().map_err(|_| MovieError::InvalidLine(number))?),
+                [cycle, button, state] => {
+                    let cycle = cycle.parse::<u64>().map_err(|_| MovieError::InvalidLine(number))?;
+                    let button = button
+                        .parse::<JoypadButton>()
+                        .map_err(|_| MovieError::UnknownButton(button.to_string()))?;
+                    let pressed = match state {
+                        "down" => true,
+                        "up" => false,
+                        _ => return Err(MovieError::InvalidLine(number)),
+                    };
+                    events.push(MovieEvent { cycle, button, pressed });
+                }
+                _ => return Err(MovieError::InvalidLine(number)),
+            }
+        }
+
+        let (Some(model), Some(rom_crc32), Some(length)) = (model, rom_crc32, length) else {
+            return Err(MovieError::InvalidHeader);
+        };
+        let unordered = events.windows(2).any(|pair| pair[0].cycle > pair[1].cycle);
+        if unordered || checkpoints.windows(2).any(|pair: &[RamCheckpoint]| pair[0].cycle > pair[1].cycle) {
+            return Err(MovieError::UnorderedEvents);
+        }
+        Ok(Movie {
+            model,
+            socd: socd.unwrap_or_default(),
+            rom_crc32,
+            length,
+            events,
+            checkpoints,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, MovieError> {
+        let text = std::fs::read_to_string(path).map_err(|error| MovieError::ReadFailure(error.to_string()))?;
+        Movie::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MovieError> {
+        std::fs::write(path, self.to_string()).map_err(|error| MovieError::WriteFailure(error.to_string()))
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MAGIC}")?;
+        writeln!(f, "model {}", self.model.name())?;
+        writeln!(f, "socd {}", self.socd.name())?;
+        writeln!(f, "rom {:08x}", self.rom_crc32)?;
+        writeln!(f, "length {}", self.length)?;
+        for event in &self.events {
+            let state = if event.pressed { "down" } else { "up" };
+            writeln!(f, "{} {} {state}", event.cycle, event.button.name())?;
+        }
+        for checkpoint in &self.checkpoints {
+            let bytes: String = checkpoint.expected.iter().map(|byte| format!("{byte:02x}")).collect();
+            writeln!(f, "check {} {:04x} {bytes}", checkpoint.cycle, checkpoint.address)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_checkpoint(cycle: u64, address: &str, bytes: &str) -> Option<RamCheckpoint> {
+    let address = u16::from_str_radix(address.trim_start_matches("0x"), 16).ok()?;
+    let bytes = bytes.trim_start_matches("0x");
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let expected = (0..bytes.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(bytes.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(RamCheckpoint { cycle, address, expected })
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MovieError {
+    #[error("Movie file could not be read: `{0}`")]
+    ReadFailure(String),
+    #[error("Movie file could not be written: `{0}`")]
+    WriteFailure(String),
+    #[error("Movie header is missing or incomplete")]
+    InvalidHeader,
+    #[error("Movie line {0} is not a header field or a `<cycle> <button> <down|up>` event")]
+    InvalidLine(usize),
+    #[error("Unknown button `{0}`")]
+    UnknownButton(String),
+    #[error("Movie events are not in cycle order")]
+    UnorderedEvents,
+    #[error("Movie was recorded on another ROM (CRC32 {expected:08x}, loaded {actual:08x})")]
+    WrongRom { expected: u32, actual: u32 },
+    #[error("Movie was recorded on {expected}, running {actual}")]
+    WrongModel { expected: String, actual: String },
+    #[error("Movies can only start at power-on")]
+    NotAtPowerOn,
+    #[error("Not an Iron Boy, BizHawk .bk2 or VBA .vbm movie")]
+    UnknownFormat,
+    #[error("Movie file ends before all of its frames")]
+    Truncated,
+    #[error("Movie needs {0}, which is not supported")]
+    Unsupported(String),
+    #[error("Movie is for ROM checksums {expected_header:02x}/{expected_global:04x}, not {actual_header:02x}/{actual_global:04x}")]
+    WrongChecksums {
+        expected_header: u8,
+        expected_global: u16,
+        actual_header: u8,
+        actual_global: u16,
+    },
+}
+
+// Replays the events of a movie as the emulated cycles reach them
+pub(crate) struct MoviePlayback {
+    movie: Movie,
+    next: usize,
+    next_checkpoint: usize,
+    pressed: u8,
+}
+
+impl MoviePlayback {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayback {
+            movie,
+            next: 0,
+            next_checkpoint: 0,
+            pressed: 0,
+        }
+    }
+
+    // Applies the events up to `cycle`, true when the buttons changed
+    pub fn advance(&mut self, cycle: u64) -> bool {
+        let mut changed = false;
+        while let Some(event) = self.movie.events.get(self.next).filter(|event| event.cycle <= cycle) {
+            match event.pressed {
+                true => self.pressed |= bit(event.button),
+                false => self.pressed &= !bit(event.button),
+            }
+            self.next += 1;
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn pressed(&self) -> u8 {
+        self.pressed
+    }
+
+    // Next checkpoint `cycle` has reached, each one is handed out once
+    pub fn checkpoint_due(&mut self, cycle: u64) -> Option<RamCheckpoint> {
+        let checkpoint = self
+            .movie
+            .checkpoints
+            .get(self.next_checkpoint)
+            .filter(|checkpoint| checkpoint.cycle <= cycle)?;
+        self.next_checkpoint += 1;
+        Some(checkpoint.clone())
+    }
+
+    pub fn finished(&self, cycle: u64) -> bool {
+        self.next >= self.movie.events.len() && cycle >= self.movie.length
+    }
+}
+
+pub(crate) enum MovieState {
+    Recording(Movie),
+    Playing(MoviePlayback),
+}
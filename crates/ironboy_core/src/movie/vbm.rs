@@ -0,0 +1,69 @@
+use ironboy_common::Model;
+
+use super::{ImportedMovie, MovieError};
+use crate::{JoypadButton, input::bit};
+
+const HEADER_SIZE: usize = 0x40;
+const FROM_SAVE_STATE: u8 = 1 << 0;
+const FROM_SAVE_DATA: u8 = 1 << 1;
+const SYSTEM_GBA: u8 = 1 << 0;
+const SYSTEM_GBC: u8 = 1 << 1;
+const SYSTEM_SGB: u8 = 1 << 2;
+const RESET: u16 = 1 << 11;
+
+// Bits of the 16-bit joypad word VBA stores for every controller and frame
+const BUTTONS: [(u16, JoypadButton); 8] = [
+    (1 << 0, JoypadButton::A),
+    (1 << 1, JoypadButton::B),
+    (1 << 2, JoypadButton::Select),
+    (1 << 3, JoypadButton::Start),
+    (1 << 4, JoypadButton::Right),
+    (1 << 5, JoypadButton::Left),
+    (1 << 6, JoypadButton::Up),
+    (1 << 7, JoypadButton::Down),
+];
+
+pub fn parse(data: &[u8]) -> Result<ImportedMovie, MovieError> {
+    if data.len() < HEADER_SIZE {
+        return Err(MovieError::Truncated);
+    }
+    let word = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+    let frame_count = word(0x0C) as usize;
+    if data[0x14] & FROM_SAVE_STATE != 0 || data[0x14] & FROM_SAVE_DATA != 0 {
+        return Err(MovieError::Unsupported("a save state or save data to start from".to_string()));
+    }
+    // Only the first controller is connected to a Game Boy, the others still take space in every frame
+    let controllers = (data[0x15] & 0x0F).count_ones().max(1) as usize;
+    let model = match data[0x16] {
+        flags if flags & SYSTEM_GBA != 0 => Model::Agb,
+        flags if flags & SYSTEM_GBC != 0 => Model::Cgb,
+        flags if flags & SYSTEM_SGB != 0 => Model::Sgb,
+        _ => Model::Dmg,
+    };
+    // The global checksum is stored with its bytes in ROM order
+    let checksums = (data[0x31], u16::from_be_bytes([data[0x32], data[0x33]]));
+
+    let input_offset = word(0x3C) as usize;
+    let frame_size = controllers * 2;
+    let input = data
+        .get(input_offset..input_offset + frame_count * frame_size)
+        .ok_or(MovieError::Truncated)?;
+    let mut frames = Vec::with_capacity(frame_count);
+    for (index, frame) in input.chunks_exact(frame_size).enumerate() {
+        let state = u16::from_le_bytes([frame[0], frame[1]]);
+        if state & RESET != 0 && index > 0 {
+            return Err(MovieError::Unsupported("a reset".to_string()));
+        }
+        let pressed = BUTTONS
+            .iter()
+            .filter(|(mask, _)| state & mask != 0)
+            .fold(0, |pressed, &(_, button)| pressed | bit(button));
+        frames.push(pressed);
+    }
+
+    Ok(ImportedMovie {
+        model,
+        frames,
+        checksums: Some(checksums),
+    })
+}
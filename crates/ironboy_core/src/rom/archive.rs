@@ -0,0 +1,105 @@
+use std::io::{Cursor, Read};
+
+use flate2::read::GzDecoder;
+use sevenz_rust::{Password, SevenZReader};
+use zip::ZipArchive;
+
+use super::RomError;
+
+const ZIP_MAGIC: &[u8] = &[0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const SEVEN_ZIP_MAGIC: &[u8] = &[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ArchiveFormat {
+    Zip,
+    Gzip,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    // Detected from the file contents so misnamed archives still open
+    pub fn detect(data: &[u8]) -> Option<ArchiveFormat> {
+        if data.starts_with(ZIP_MAGIC) {
+            Some(ArchiveFormat::Zip)
+        } else if data.starts_with(GZIP_MAGIC) {
+            Some(ArchiveFormat::Gzip)
+        } else if data.starts_with(SEVEN_ZIP_MAGIC) {
+            Some(ArchiveFormat::SevenZip)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct ArchiveEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub fn is_rom_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [".gb", ".gbc", ".cgb", ".sgb"].iter().any(|extension| name.ends_with(extension))
+}
+
+// Returns every ROM in the archive, `fallback_name` names the content of single file formats
+pub fn rom_entries(format: ArchiveFormat, data: &[u8], fallback_name: &str) -> Result<Vec<ArchiveEntry>, RomError> {
+    match format {
+        ArchiveFormat::Zip => zip_entries(data),
+        ArchiveFormat::Gzip => gzip_entry(data, fallback_name).map(|entry| vec![entry]),
+        ArchiveFormat::SevenZip => seven_zip_entries(data),
+    }
+}
+
+fn zip_entries(data: &[u8]) -> Result<Vec<ArchiveEntry>, RomError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+        if file.is_dir() || !is_rom_name(file.name()) {
+            continue;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+        entries.push(ArchiveEntry {
+            name: file.name().to_string(),
+            data,
+        });
+    }
+    Ok(entries)
+}
+
+fn gzip_entry(data: &[u8], fallback_name: &str) -> Result<ArchiveEntry, RomError> {
+    let mut decoder = GzDecoder::new(data);
+    let mut rom = Vec::new();
+    decoder
+        .read_to_end(&mut rom)
+        .map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+    let name = decoder
+        .header()
+        .and_then(|header| header.filename())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_else(|| fallback_name.to_string());
+    Ok(ArchiveEntry { name, data: rom })
+}
+
+fn seven_zip_entries(data: &[u8]) -> Result<Vec<ArchiveEntry>, RomError> {
+    let mut reader = SevenZReader::new(Cursor::new(data), data.len() as u64, Password::empty())
+        .map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+    let mut entries = Vec::new();
+    reader
+        .for_each_entries(|entry, content| {
+            if !entry.is_directory() && is_rom_name(entry.name()) {
+                let mut data = Vec::new();
+                content.read_to_end(&mut data)?;
+                entries.push(ArchiveEntry {
+                    name: entry.name().to_string(),
+                    data,
+                });
+            }
+            Ok(true)
+        })
+        .map_err(|error| RomError::ArchiveFailure(error.to_string()))?;
+    Ok(entries)
+}
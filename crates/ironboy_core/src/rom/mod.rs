@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+pub use archive::ArchiveFormat;
+pub use patch::PatchFormat;
+
+mod archive;
+mod patch;
+
+pub struct RomFile {
+    // Path the save file is derived from, the ROM's own path or the archive entry next to the archive
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    // Patch that was applied on top of the ROM, if any
+    pub patch: Option<PathBuf>,
+}
+
+impl RomFile {
+    pub fn name(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+}
+
+// Reads a ROM like `read_rom` and applies a BPS, UPS or IPS patch with the same name as the ROM
+// or its archive. The patched game gets its own save file so it never clobbers the original's.
+pub fn load_rom<F>(path: &Path, choose: F) -> Result<RomFile, RomError>
+where
+    F: FnOnce(&[String]) -> Option<usize>,
+{
+    let mut rom = read_rom(path, choose)?;
+    let mut candidates = PatchFormat::ALL.iter().flat_map(|format| {
+        [rom.path.with_extension(format.extension()), path.with_extension(format.extension())]
+            .into_iter()
+            .map(move |patch_path| (*format, patch_path))
+    });
+    let Some((format, patch_path)) = candidates.find(|(_, patch_path)| patch_path.is_file()) else {
+        return Ok(rom);
+    };
+
+    let patch = fs::read(&patch_path).map_err(|error| RomError::ReadFailure(error.to_string()))?;
+    rom.data = format.apply(&rom.data, &patch)?;
+    let extension = rom.path.extension().map(|extension| extension.to_string_lossy().into_owned()).unwrap_or_default();
+    rom.path = patch_path.with_extension(format!("patched.{extension}"));
+    rom.patch = Some(patch_path);
+    Ok(rom)
+}
+
+// Reads a ROM from disk, unpacking zip, gzip and 7z archives. When an archive holds several
+// ROMs `choose` receives their names and returns the index of the one to run.
+pub fn read_rom<F>(path: &Path, choose: F) -> Result<RomFile, RomError>
+where
+    F: FnOnce(&[String]) -> Option<usize>,
+{
+    let data = fs::read(path).map_err(|error| RomError::ReadFailure(error.to_string()))?;
+    unpack_rom(path, data, choose)
+}
+
+// Unpacks the ROM from file contents already read from `path`
+pub(crate) fn unpack_rom<F>(path: &Path, data: Vec<u8>, choose: F) -> Result<RomFile, RomError>
+where
+    F: FnOnce(&[String]) -> Option<usize>,
+{
+    let Some(format) = ArchiveFormat::detect(&data) else {
+        return Ok(RomFile {
+            path: path.to_path_buf(),
+            data,
+            patch: None,
+        });
+    };
+
+    // `game.gb.gz` without a stored name still unpacks to `game.gb`
+    let fallback_name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut entries = archive::rom_entries(format, &data, &fallback_name)?;
+    let index = match entries.len() {
+        0 => return Err(RomError::NoRomInArchive),
+        1 => 0,
+        _ => {
+            let names: Vec<String> = entries.iter().map(|entry| entry.name.clone()).collect();
+            match choose(&names) {
+                Some(index) if index < entries.len() => index,
+                _ => return Err(RomError::NoRomSelected),
+            }
+        }
+    };
+
+    let entry = entries.swap_remove(index);
+    // Only the file name is kept so saves land next to the archive whatever folder the entry was in
+    let file_name = Path::new(&entry.name).file_name().map(PathBuf::from).unwrap_or_default();
+    Ok(RomFile {
+        path: path.with_file_name(file_name),
+        data: entry.data,
+        patch: None,
+    })
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RomError {
+    #[error("ROM could not be read: `{0}`")]
+    ReadFailure(String),
+    #[error("Archive could not be extracted: `{0}`")]
+    ArchiveFailure(String),
+    #[error("Archive does not contain a .gb or .gbc file")]
+    NoRomInArchive,
+    #[error("No ROM was selected from the archive")]
+    NoRomSelected,
+    #[error("Patch is malformed: `{0}`")]
+    InvalidPatch(String),
+    #[error("Patch checksum does not match, the patch file is corrupt")]
+    PatchChecksumMismatch,
+    #[error("Patch was made for a different ROM")]
+    PatchSourceMismatch,
+    #[error("Patched ROM checksum does not match the patch")]
+    PatchTargetMismatch,
+}
@@ -0,0 +1,247 @@
+use super::RomError;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+const FOOTER_SIZE: usize = 12;
+// Largest ROM a cartridge header can describe
+const MAX_ROM_SIZE: usize = 0x80_0000;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    // Checked in this order when looking for a patch next to the ROM
+    pub const ALL: [PatchFormat; 3] = [PatchFormat::Bps, PatchFormat::Ups, PatchFormat::Ips];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PatchFormat::Ips => "ips",
+            PatchFormat::Ups => "ups",
+            PatchFormat::Bps => "bps",
+        }
+    }
+
+    pub fn apply(&self, rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, RomError> {
+        match self {
+            PatchFormat::Ips => apply_ips(rom, patch),
+            PatchFormat::Ups => apply_ups(rom, patch),
+            PatchFormat::Bps => apply_bps(rom, patch),
+        }
+    }
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        PatchReader { data, position }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], RomError> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| RomError::InvalidPatch("Unexpected end of patch".to_string()))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, RomError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, RomError> {
+        Ok(self.bytes(count)?.iter().fold(0, |value, byte| (value << 8) | *byte as usize))
+    }
+
+    // Variable length integer shared by UPS and BPS
+    fn number(&mut self) -> Result<usize, RomError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|part| value.checked_add(part))
+                .ok_or_else(|| RomError::InvalidPatch("Number overflow".to_string()))?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|shift| *shift != 0)
+                .ok_or_else(|| RomError::InvalidPatch("Number overflow".to_string()))?;
+            value += shift;
+        }
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, RomError> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(RomError::InvalidPatch("Missing IPS header".to_string()));
+    }
+
+    let mut output = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+    loop {
+        let offset = reader.bytes(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let offset = offset.iter().fold(0, |value, byte| (value << 8) | *byte as usize);
+        let (data, length) = match reader.big_endian(2)? {
+            0 => {
+                let length = reader.big_endian(2)?;
+                (None, length)
+            }
+            length => (Some(reader.bytes(length)?), length),
+        };
+
+        if output.len() < offset + length {
+            output.resize(offset + length, 0);
+        }
+        match data {
+            Some(data) => output[offset..offset + length].copy_from_slice(data),
+            None => {
+                let value = reader.byte()?;
+                output[offset..offset + length].fill(value);
+            }
+        }
+    }
+
+    // Optional truncation extension after the EOF marker
+    if let Ok(size) = reader.big_endian(3) {
+        output.truncate(size);
+    }
+    Ok(output)
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, RomError> {
+    let target_crc = verify_footer(UPS_MAGIC, rom, patch)?;
+
+    let mut reader = PatchReader::new(patch, UPS_MAGIC.len());
+    let source_size = reader.number()?;
+    if source_size != rom.len() {
+        return Err(RomError::PatchSourceMismatch);
+    }
+    let target_size = reader.number()?;
+    if target_size > MAX_ROM_SIZE {
+        return Err(RomError::InvalidPatch("Patched ROM is too large".to_string()));
+    }
+
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut offset: usize = 0;
+    while reader.position < patch.len() - FOOTER_SIZE {
+        offset = offset.saturating_add(reader.number()?);
+        loop {
+            let value = reader.byte()?;
+            if value == 0 {
+                offset += 1;
+                break;
+            }
+            if let Some(byte) = output.get_mut(offset) {
+                *byte ^= value;
+            }
+            offset += 1;
+        }
+    }
+
+    verify_target(&output, target_crc)?;
+    Ok(output)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, RomError> {
+    let target_crc = verify_footer(BPS_MAGIC, rom, patch)?;
+
+    let mut reader = PatchReader::new(patch, BPS_MAGIC.len());
+    let _source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    if target_size > MAX_ROM_SIZE {
+        return Err(RomError::InvalidPatch("Patched ROM is too large".to_string()));
+    }
+
+    let invalid = || RomError::InvalidPatch("Copy outside of ROM".to_string());
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    while reader.position < patch.len() - FOOTER_SIZE {
+        let data = reader.number()?;
+        let length = (data >> 2) + 1;
+        if output.len() + length > target_size {
+            return Err(RomError::InvalidPatch("Patched ROM has the wrong size".to_string()));
+        }
+        match data & 0x03 {
+            0 => {
+                let start = output.len();
+                output.extend_from_slice(rom.get(start..start + length).ok_or_else(invalid)?);
+            }
+            1 => output.extend_from_slice(reader.bytes(length)?),
+            2 => {
+                source_offset = source_offset.saturating_add(signed_offset(reader.number()?));
+                let start = usize::try_from(source_offset).map_err(|_| invalid())?;
+                output.extend_from_slice(rom.get(start..start + length).ok_or_else(invalid)?);
+                source_offset += length as isize;
+            }
+            _ => {
+                target_offset = target_offset.saturating_add(signed_offset(reader.number()?));
+                // Byte by byte, the copied range may overlap the bytes being written
+                for _ in 0..length {
+                    let index = usize::try_from(target_offset).map_err(|_| invalid())?;
+                    let value = *output.get(index).ok_or_else(invalid)?;
+                    output.push(value);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if output.len() != target_size {
+        return Err(RomError::InvalidPatch("Patched ROM has the wrong size".to_string()));
+    }
+    verify_target(&output, target_crc)?;
+    Ok(output)
+}
+
+fn signed_offset(value: usize) -> isize {
+    let magnitude = (value >> 1) as isize;
+    if value & 0x01 != 0 { -magnitude } else { magnitude }
+}
+
+// Checks the patch itself and the unpatched ROM against the CRC32 footer
+fn verify_footer(magic: &[u8], rom: &[u8], patch: &[u8]) -> Result<u32, RomError> {
+    if !patch.starts_with(magic) || patch.len() < magic.len() + FOOTER_SIZE {
+        return Err(RomError::InvalidPatch("Missing patch header".to_string()));
+    }
+
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let read_crc = |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+    let (source_crc, target_crc, patch_crc) = (read_crc(0), read_crc(1), read_crc(2));
+
+    if crc32fast::hash(&patch[..patch.len() - 4]) != patch_crc {
+        return Err(RomError::PatchChecksumMismatch);
+    }
+    if crc32fast::hash(rom) != source_crc {
+        return Err(RomError::PatchSourceMismatch);
+    }
+    Ok(target_crc)
+}
+
+fn verify_target(output: &[u8], target_crc: u32) -> Result<(), RomError> {
+    match crc32fast::hash(output) == target_crc {
+        true => Ok(()),
+        false => Err(RomError::PatchTargetMismatch),
+    }
+}
@@ -0,0 +1,697 @@
+use flate2::{Compression, GzBuilder};
+use ironboy_common::{CPU_CLOCK_SPEED, MemoryInterface};
+use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+use std::{
+    io::{Cursor, Write},
+    path::Path,
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    CartridgeError, CpuState, Filter, FrameBuffer, GameBoyError, GameBoyEvent, HighPass, IllegalOpcode, JoypadButton, LoadMode, Model, TiltDirection,
+    gb::{GameBoy, GameBoyOptions},
+    input::{InputMacro, Inputs, MacroError, MacroStep},
+    movie::{FRAME_CYCLES, Movie, MovieError, MovieEvent},
+    rom::{PatchFormat, RomError, RomFile, unpack_rom},
+};
+
+fn encode_number(mut value: usize, output: &mut Vec<u8>) {
+    loop {
+        let bits = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(0x80 | bits);
+            return;
+        }
+        output.push(bits);
+        value -= 1;
+    }
+}
+
+fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let patch_crc = crc32fast::hash(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    patch
+}
+
+fn ups_patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = b"UPS1".to_vec();
+    encode_number(source.len(), &mut patch);
+    encode_number(target.len(), &mut patch);
+    let mut last = 0;
+    let mut offset = 0;
+    while offset < target.len() {
+        let xor = |offset: usize| source.get(offset).copied().unwrap_or(0) ^ target[offset];
+        if xor(offset) == 0 {
+            offset += 1;
+            continue;
+        }
+        encode_number(offset - last, &mut patch);
+        while offset < target.len() && xor(offset) != 0 {
+            patch.push(xor(offset));
+            offset += 1;
+        }
+        patch.push(0);
+        offset += 1;
+        last = offset;
+    }
+    with_footer(patch, source, target)
+}
+
+#[test]
+fn ips_patch_writes_records_and_run_lengths() {
+    let rom = vec![0u8; 8];
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);
+    patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+    patch.extend_from_slice(b"EOF");
+
+    let patched = PatchFormat::Ips.apply(&rom, &patch).unwrap();
+    assert_eq!(patched, vec![0, 0, 0xAA, 0xBB, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC]);
+}
+
+#[test]
+fn ips_patch_truncates_after_eof() {
+    let rom = vec![1u8; 8];
+    let mut patch = b"PATCHEOF".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+
+    let patched = PatchFormat::Ips.apply(&rom, &patch).unwrap();
+    assert_eq!(patched, vec![1, 1, 1, 1]);
+}
+
+#[test]
+fn ups_patch_round_trips() {
+    let source: Vec<u8> = (0..=255).collect();
+    let mut target = source.clone();
+    target[10] = 0;
+    target[11] = 0x42;
+    target.extend_from_slice(&[7, 8, 9]);
+
+    let patch = ups_patch(&source, &target);
+    assert_eq!(PatchFormat::Ups.apply(&source, &patch).unwrap(), target);
+}
+
+#[test]
+fn ups_patch_rejects_other_rom() {
+    let source = vec![1u8; 32];
+    let target = vec![2u8; 32];
+    let patch = ups_patch(&source, &target);
+
+    assert_eq!(PatchFormat::Ups.apply(&[3u8; 32], &patch), Err(RomError::PatchSourceMismatch));
+}
+
+#[test]
+fn ups_patch_rejects_rom_of_another_size() {
+    let source = vec![1u8; 32];
+    let mut patch = b"UPS1".to_vec();
+    encode_number(source.len() + 1, &mut patch);
+    encode_number(source.len(), &mut patch);
+    let patch = with_footer(patch, &source, &source);
+
+    assert_eq!(PatchFormat::Ups.apply(&source, &patch), Err(RomError::PatchSourceMismatch));
+}
+
+#[test]
+fn bps_patch_applies_every_action() {
+    let source = b"ABCDEFGH".to_vec();
+    let target = b"ABCDxyxyxyEF".to_vec();
+
+    let mut patch = b"BPS1".to_vec();
+    encode_number(source.len(), &mut patch);
+    encode_number(target.len(), &mut patch);
+    encode_number(0, &mut patch);
+    // SourceRead "ABCD"
+    encode_number(3 << 2, &mut patch);
+    // TargetRead "xy"
+    encode_number((1 << 2) | 1, &mut patch);
+    patch.extend_from_slice(b"xy");
+    // TargetCopy "xyxy" from offset 4, overlapping its own output
+    encode_number((3 << 2) | 3, &mut patch);
+    encode_number(4 << 1, &mut patch);
+    // SourceCopy "EF" from offset 4
+    encode_number((1 << 2) | 2, &mut patch);
+    encode_number(4 << 1, &mut patch);
+    let patch = with_footer(patch, &source, &target);
+
+    assert_eq!(PatchFormat::Bps.apply(&source, &patch).unwrap(), target);
+}
+
+#[test]
+fn bps_patch_rejects_corrupt_patch() {
+    let source = b"ABCD".to_vec();
+    let mut patch = b"BPS1".to_vec();
+    encode_number(4, &mut patch);
+    encode_number(4, &mut patch);
+    encode_number(0, &mut patch);
+    encode_number(3 << 2, &mut patch);
+    let mut patch = with_footer(patch, &source, &source);
+    let last = patch.len() - 1;
+    patch[last] ^= 0xFF;
+
+    assert_eq!(PatchFormat::Bps.apply(&source, &patch), Err(RomError::PatchChecksumMismatch));
+}
+
+fn zip_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        archive.start_file(*name, SimpleFileOptions::default()).unwrap();
+        archive.write_all(data).unwrap();
+    }
+    archive.finish().unwrap().into_inner()
+}
+
+fn unpack(name: &str, data: Vec<u8>) -> Result<RomFile, RomError> {
+    unpack_rom(Path::new("roms").join(name).as_path(), data, |_| panic!("only one ROM to choose from"))
+}
+
+#[test]
+fn zip_archives_unpack_their_rom() {
+    let archive = zip_archive(&[("readme.txt", b"hello"), ("games/tetris.gb", b"ROM")]);
+    let rom = unpack("tetris.zip", archive).unwrap();
+    assert_eq!(rom.path, Path::new("roms/tetris.gb"));
+    assert_eq!(rom.data, b"ROM");
+
+    assert_eq!(
+        unpack("empty.zip", zip_archive(&[("readme.txt", b"hello")])).err(),
+        Some(RomError::NoRomInArchive)
+    );
+    // Anything that isn't an archive is taken as the ROM itself
+    assert_eq!(unpack("game.gb", b"ROM".to_vec()).unwrap().data, b"ROM");
+}
+
+#[test]
+fn archives_with_several_roms_ask_which_to_run() {
+    let archive = zip_archive(&[("red.gb", b"RED"), ("blue.gbc", b"BLUE")]);
+    let rom = unpack_rom(Path::new("pokemon.zip"), archive.clone(), |names| {
+        assert_eq!(names, ["red.gb", "blue.gbc"]);
+        Some(1)
+    })
+    .unwrap();
+    assert_eq!(rom.path, Path::new("blue.gbc"));
+    assert_eq!(rom.data, b"BLUE");
+
+    for choice in [None, Some(2)] {
+        let result = unpack_rom(Path::new("pokemon.zip"), archive.clone(), |_| choice);
+        assert_eq!(result.err(), Some(RomError::NoRomSelected));
+    }
+}
+
+#[test]
+fn gzip_archives_use_the_stored_or_file_name() {
+    let gzip = |filename: Option<&str>| {
+        let builder = filename.map_or_else(GzBuilder::new, |filename| GzBuilder::new().filename(filename));
+        let mut encoder = builder.write(Vec::new(), Compression::default());
+        encoder.write_all(b"ROM").unwrap();
+        encoder.finish().unwrap()
+    };
+    let rom = unpack("archive.gz", gzip(Some("tetris.gb"))).unwrap();
+    assert_eq!(rom.path, Path::new("roms/tetris.gb"));
+    assert_eq!(rom.data, b"ROM");
+    assert_eq!(unpack("tetris.gb.gz", gzip(None)).unwrap().path, Path::new("roms/tetris.gb"));
+    assert!(matches!(unpack("broken.gz", vec![0x1F, 0x8B, 0x00]), Err(RomError::ArchiveFailure(_))));
+}
+
+#[test]
+fn seven_zip_archives_unpack_their_rom() {
+    let mut writer = SevenZWriter::new(Cursor::new(Vec::new())).unwrap();
+    for (name, data) in [("readme.txt", &b"hello"[..]), ("tetris.gb", b"ROM")] {
+        let mut entry = SevenZArchiveEntry::new();
+        entry.name = name.to_string();
+        entry.has_stream = true;
+        writer.push_archive_entry(entry, Some(data)).unwrap();
+    }
+    let archive = writer.finish().unwrap().into_inner();
+    let rom = unpack("tetris.7z", archive).unwrap();
+    assert_eq!(rom.path, Path::new("roms/tetris.gb"));
+    assert_eq!(rom.data, b"ROM");
+}
+
+fn frame(width: usize, height: usize, pixels: &[u32]) -> FrameBuffer {
+    let mut frame = FrameBuffer::new(width, height, false);
+    frame.pixels_mut().copy_from_slice(pixels);
+    frame
+}
+
+#[test]
+fn filters_scale_the_frame() {
+    let frame = frame(2, 2, &[1, 2, 3, 4]);
+    for filter in Filter::ALL {
+        let mut output = FrameBuffer::new(0, 0, false);
+        filter.apply(&frame, &mut output);
+        assert_eq!((output.width(), output.height()), (2 * filter.scale(), 2 * filter.scale()));
+    }
+}
+
+#[test]
+fn scale2x_rounds_diagonal_edges() {
+    // Black triangle in the top left corner of a white frame
+    let (w, b) = (0xFFFFFF, 0x000000);
+    let frame = frame(3, 3, &[b, b, w, b, w, w, w, w, w]);
+    let mut output = FrameBuffer::new(0, 0, false);
+    Filter::Scale2x.apply(&frame, &mut output);
+
+    // The center pixel gets its top left quarter filled in
+    let center = |x: usize, y: usize| output.pixels()[(2 + y) * 6 + 2 + x];
+    assert_eq!([center(0, 0), center(1, 0), center(0, 1), center(1, 1)], [b, w, w, w]);
+}
+
+#[test]
+fn input_macro_round_trips() {
+    let text = "# title screen\n2 a+start\n3 -\n1 up\n";
+    let input_macro = InputMacro::parse(text).unwrap();
+    assert_eq!(
+        input_macro.steps(),
+        [
+            MacroStep {
+                buttons: vec![JoypadButton::A, JoypadButton::Start],
+                frames: 2
+            },
+            MacroStep { buttons: vec![], frames: 3 },
+            MacroStep {
+                buttons: vec![JoypadButton::Up],
+                frames: 1
+            },
+        ]
+    );
+    assert_eq!(input_macro.frames(), 6);
+    assert_eq!(InputMacro::parse(&input_macro.to_string()).unwrap(), input_macro);
+    assert_eq!(InputMacro::parse("2 a+jump"), Err(MacroError::UnknownButton("jump".to_string())));
+    assert_eq!(InputMacro::parse("a"), Err(MacroError::InvalidLine(1)));
+}
+
+#[test]
+fn turbo_and_macros_drive_the_pressed_buttons() {
+    let mut inputs = Inputs::new();
+    inputs.set_turbo_rate(10);
+    inputs.set_turbo(JoypadButton::A, true);
+    let mut turbo = Vec::new();
+    for _ in 0..8 {
+        turbo.push(inputs.pressed() != 0);
+        inputs.next_frame();
+    }
+    assert_eq!(turbo, [true, true, true, false, false, false, true, true]);
+    inputs.set_turbo(JoypadButton::A, false);
+
+    inputs.start_recording();
+    inputs.play(InputMacro::parse("2 b\n1 -\n1 select").unwrap());
+    for _ in 0..4 {
+        inputs.next_frame();
+    }
+    assert!(!inputs.playing());
+    let recorded = inputs.stop_recording().unwrap();
+    assert_eq!(recorded.to_string(), "2 b\n1 -\n1 select\n");
+}
+
+// Reads P1 with the d-pad selected and stores every value to WRAM, wrapping at 0xD000
+fn joypad_logger() -> GameBoy {
+    let mut rom = vec![0; 0x8000];
+    let program = [
+        0x21, 0x00, 0xC0, 0x3E, 0x20, 0xE0, 0x00, 0xF0, 0x00, 0x22, 0x7C, 0xFE, 0xD0, 0x20, 0xF4, 0x26, 0xC0, 0x18, 0xF0,
+    ];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        ..Default::default()
+    };
+    GameBoy::with_options("logger.gb", rom, options).unwrap()
+}
+
+fn work_ram(game_boy: &GameBoy) -> Vec<u8> {
+    (0xC000..0xD000).map(|address| game_boy.cpu.load_8(address)).collect()
+}
+
+#[test]
+fn movie_replays_the_recorded_session() {
+    let mut recorder = joypad_logger();
+    recorder.start_movie_recording().unwrap();
+    let mut recorded = Vec::new();
+    for frame in 0..12 {
+        match frame {
+            2 => recorder.button_down(JoypadButton::Right),
+            5 => recorder.turbo_down(JoypadButton::Down),
+            8 => recorder.button_up(JoypadButton::Right),
+            _ => {}
+        }
+        recorder.run();
+        recorded.push(work_ram(&recorder));
+    }
+    let movie = recorder.stop_movie_recording().unwrap();
+    // Right alone, then Right with Down
+    assert!(recorded.iter().flatten().any(|&value| value == 0xEE));
+    assert!(recorded.iter().flatten().any(|&value| value == 0xE6));
+    assert_eq!(movie.length, recorder.cycles());
+    let movie = Movie::parse(&movie.to_string()).unwrap();
+
+    let mut player = joypad_logger();
+    player.play_movie(movie.clone()).unwrap();
+    let mut played = Vec::new();
+    for _ in 0..12 {
+        // Ignored while the movie plays
+        player.button_down(JoypadButton::Up);
+        player.run();
+        played.push(work_ram(&player));
+    }
+    assert!(played == recorded);
+    assert_eq!(player.cycles(), recorder.cycles());
+    assert!(!player.movie_playing());
+
+    let mut late = joypad_logger();
+    late.run();
+    assert_eq!(late.play_movie(movie), Err(MovieError::NotAtPowerOn.into()));
+}
+
+fn vbm_movie(frames: &[u16]) -> Vec<u8> {
+    let mut data = vec![0; 0x40];
+    data[..4].copy_from_slice(b"VBM\x1A");
+    data[0x0C..0x10].copy_from_slice(&(frames.len() as u32).to_le_bytes());
+    data[0x15] = 0x01;
+    data[0x16] = 0x02;
+    data[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    data.extend(frames.iter().flat_map(|frame| frame.to_le_bytes()));
+    data
+}
+
+fn bk2_movie(header: &str, input_log: &str) -> Vec<u8> {
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, text) in [("Header.txt", header), ("Input Log.txt", input_log)] {
+        archive.start_file(name, SimpleFileOptions::default()).unwrap();
+        archive.write_all(text.as_bytes()).unwrap();
+    }
+    archive.finish().unwrap().into_inner()
+}
+
+#[test]
+fn imported_movies_hold_each_frame() {
+    let imported = Movie::import(&vbm_movie(&[0x0001, 0x0011, 0x0000])).unwrap();
+    assert_eq!(imported.model, Model::Cgb);
+    let movie = Movie::from_frames(imported.model, 0, &imported.frames);
+    let event = |frame: u64, button, pressed| MovieEvent {
+        cycle: Movie::frame_start(frame),
+        button,
+        pressed,
+    };
+    assert_eq!(
+        movie.events,
+        [
+            event(0, JoypadButton::A, true),
+            event(1, JoypadButton::Right, true),
+            event(2, JoypadButton::Right, false),
+            event(2, JoypadButton::A, false),
+        ]
+    );
+    assert_eq!(movie.length, Movie::frame_start(3));
+
+    let input_log = "[Input]\nLogKey:#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|P1 Power|\n\
+                     |.........|\n|...R...A.|\n|.......A.|\n[/Input]\n";
+    let bk2 = Movie::import(&bk2_movie("MovieVersion BizHawk v2.0\nPlatform GB\n", input_log)).unwrap();
+    assert_eq!(bk2.model, Model::Dmg);
+    let bk2 = Movie::from_frames(bk2.model, 0, &bk2.frames);
+    assert_eq!(
+        bk2.events,
+        [
+            event(1, JoypadButton::Right, true),
+            event(1, JoypadButton::A, true),
+            event(2, JoypadButton::Right, false),
+        ]
+    );
+
+    assert_eq!(Movie::import(&bk2_movie("Platform GBC\n", input_log)).unwrap().model, Model::Cgb);
+    assert_eq!(
+        Movie::import(&bk2_movie("Platform GB\nIsCGBMode True\n", input_log)).unwrap().model,
+        Model::Cgb
+    );
+
+    let power_cycle = "[Input]\nLogKey:#Up|Power|\n|..|\n|.P|\n[/Input]\n";
+    assert!(matches!(
+        Movie::import(&bk2_movie("Platform GB\n", power_cycle)),
+        Err(MovieError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn imported_frames_end_at_vblank() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        ..Default::default()
+    };
+    let mut game_boy = GameBoy::with_options("vblank.gb", rom, options).unwrap();
+    for frame in 1..4 {
+        let start = Movie::frame_start(frame);
+        assert_eq!(Movie::frame_at(start - 1), frame - 1);
+        assert_eq!(Movie::frame_at(start), frame);
+        // JR takes 12 cycles, LY is read within one of them around the frame start
+        while game_boy.cycles() + 12 < start {
+            game_boy.cpu.cycle();
+        }
+        assert_eq!(game_boy.cpu.load_8(0xFF44), 143);
+        while game_boy.cycles() < start {
+            game_boy.cpu.cycle();
+        }
+        assert_eq!(game_boy.cpu.load_8(0xFF44), 144);
+    }
+}
+
+#[test]
+fn movie_clock_ignores_double_speed() {
+    let mut rom = vec![0; 0x8000];
+    // Switches to double speed and spins
+    let program = [0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00, 0x18, 0xFE];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    rom[0x143] = 0x80;
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        model: Some(Model::Cgb),
+        ..Default::default()
+    };
+    let mut game_boy = GameBoy::with_options("speed.gb", rom, options).unwrap();
+    game_boy.run();
+    assert_eq!(game_boy.cpu.load_8(0xFF4D) & 0x80, 0x80);
+
+    // Two frames' worth of CPU cycles only last one frame in double speed
+    let start = game_boy.cycles();
+    game_boy.run();
+    game_boy.run();
+    assert!((game_boy.cycles() - start).abs_diff(FRAME_CYCLES) < 16);
+}
+
+#[test]
+fn movie_checkpoints_report_desyncs() {
+    let mut recorder = joypad_logger();
+    recorder.start_movie_recording().unwrap();
+    for _ in 0..4 {
+        recorder.run();
+    }
+    let mut movie = recorder.stop_movie_recording().unwrap();
+    movie.parse_checkpoints("# P1 with nothing pressed\n2 C000 EFEF\n3 0xC001 00\n").unwrap();
+    let movie = Movie::parse(&movie.to_string()).unwrap();
+    assert_eq!(movie.checkpoints.len(), 2);
+
+    let mut player = joypad_logger();
+    player.play_movie(movie).unwrap();
+    for _ in 0..4 {
+        player.run();
+    }
+    let desyncs: Vec<GameBoyEvent> = std::iter::from_fn(|| player.poll_event())
+        .filter(|event| matches!(event, GameBoyEvent::MovieDesync { .. }))
+        .collect();
+    assert_eq!(
+        desyncs,
+        [GameBoyEvent::MovieDesync {
+            cycle: Movie::frame_start(3),
+            address: 0xC001,
+            expected: 0x00,
+            actual: 0xEF,
+        }]
+    );
+}
+
+// Fills OAM with its own offsets, then runs an instruction with HL pointing at it while the PPU scans the first line
+fn oam_after(opcode: u8, model: Model) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    let program = [
+        0xAF, 0xE0, 0x40, 0x21, 0x00, 0xFE, 0x7D, 0x22, 0x7D, 0xFE, 0xA0, 0x20, 0xF9, 0x3E, 0x91, 0xE0, 0x40, 0x21, 0x10, 0xFE, opcode, 0xAF, 0xE0,
+        0x40, 0x18, 0xFE,
+    ];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        model: Some(model),
+        ..Default::default()
+    };
+    let mut game_boy = GameBoy::with_options("oam.gb", rom, options).unwrap();
+    game_boy.run();
+    (0xFE00..0xFEA0).map(|address| game_boy.cpu.load_8(address)).collect()
+}
+
+#[test]
+fn oam_bug_only_hits_monochrome_models() {
+    let filled: Vec<u8> = (0..0xA0).collect();
+    // INC HL and LD A,(HL)
+    for opcode in [0x23, 0x7E] {
+        assert_ne!(oam_after(opcode, Model::Dmg), filled);
+        assert_eq!(oam_after(opcode, Model::Cgb), filled);
+    }
+    // INC L doesn't touch the bus
+    assert_eq!(oam_after(0x2C, Model::Dmg), filled);
+}
+
+// Plays a 512 Hz square wave on channel 1 at full volume in both ears
+fn square_wave() -> GameBoy {
+    let mut rom = vec![0; 0x8000];
+    let program = [
+        0x3E, 0x80, 0xE0, 0x26, 0x3E, 0x77, 0xE0, 0x24, 0x3E, 0xFF, 0xE0, 0x25, 0x3E, 0x80, 0xE0, 0x11, 0x3E, 0xF0, 0xE0, 0x12, 0x3E, 0x00, 0xE0,
+        0x13, 0x3E, 0x87, 0xE0, 0x14, 0x18, 0xFE,
+    ];
+    rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        model: Some(Model::Dmg),
+        ..Default::default()
+    };
+    GameBoy::with_options("square.gb", rom, options).unwrap()
+}
+
+#[test]
+fn audio_is_resampled_and_dc_blocked() {
+    let mut game_boy = square_wave();
+    game_boy.set_sample_rate(48000);
+    assert_eq!(game_boy.high_pass(), HighPass::Dmg);
+    let mut samples = vec![0; 1 << 16];
+    for _ in 0..30 {
+        game_boy.run();
+    }
+    game_boy.read_audio_i16(&mut samples);
+
+    game_boy.run();
+    let count = game_boy.read_audio_i16(&mut samples);
+    // One frame lasts 70224 cycles, about 804 samples per ear at 48 kHz
+    let expected = 2 * 48000 * FRAME_CYCLES as usize / CPU_CLOCK_SPEED as usize;
+    assert!(count.abs_diff(expected) < 100, "{count} samples for {expected}");
+    let left: Vec<i64> = samples[..count].iter().step_by(2).map(|&sample| sample as i64).collect();
+    let peak = left.iter().map(|sample| sample.abs()).max().unwrap();
+    let mean = left.iter().sum::<i64>() / left.len() as i64;
+    assert!(peak > 2000, "peak of {peak}");
+    assert!(mean.abs() < peak / 20, "mean of {mean} for a peak of {peak}");
+    assert_eq!(samples[..count].chunks(2).filter(|pair| pair[0] != pair[1]).count(), 0);
+}
+
+// MBC5 rumble cartridge running `program`
+fn rumble_cartridge(program: &[u8]) -> GameBoy {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x100 + program.len()].copy_from_slice(program);
+    rom[0x147] = 0x1C;
+    rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |checksum, &byte| checksum.wrapping_sub(byte).wrapping_sub(1));
+    GameBoy::new("rumble.gb", rom).unwrap()
+}
+
+fn events(game_boy: &mut GameBoy) -> Vec<GameBoyEvent> {
+    std::iter::from_fn(|| game_boy.poll_event()).collect()
+}
+
+#[test]
+fn rumble_writes_raise_events() {
+    // Switches the motor on then off through bit 3 of the RAM bank register
+    let mut game_boy = rumble_cartridge(&[0x3E, 0x08, 0xEA, 0x00, 0x40, 0xAF, 0xEA, 0x00, 0x40, 0x18, 0xFE]);
+    game_boy.run();
+    assert_eq!(events(&mut game_boy), [GameBoyEvent::Rumble(true), GameBoyEvent::Rumble(false)]);
+    assert!(!game_boy.rumble_active());
+}
+
+#[test]
+fn unpolled_events_are_capped() {
+    // Toggles the motor forever, a few thousand times a frame
+    let mut game_boy = rumble_cartridge(&[0x3E, 0x08, 0xEA, 0x00, 0x40, 0xAF, 0xEA, 0x00, 0x40, 0x18, 0xF5]);
+    game_boy.run();
+    let events = events(&mut game_boy);
+    assert_eq!(events.len(), 1024);
+    // The newest are kept, ending with the motor in its current state
+    assert_eq!(events.last(), Some(&GameBoyEvent::Rumble(game_boy.rumble_active())));
+    assert!(events.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn bad_cartridges_are_reported_instead_of_panicking() {
+    // The header checksum of an all-zero header is 0xE7
+    let mut rom = vec![0; 0x8000];
+    assert_eq!(
+        GameBoy::new("checksum.gb", rom.clone()).err(),
+        Some(GameBoyError::Cartridge(CartridgeError::CheckSumFailure))
+    );
+
+    rom[0x147] = 0xEE;
+    rom[0x14D] = 0xF9;
+    assert_eq!(
+        GameBoy::new("type.gb", rom).err(),
+        Some(GameBoyError::Cartridge(CartridgeError::InvalidCatridgeType))
+    );
+}
+
+#[test]
+fn illegal_opcodes_lock_the_cpu_while_the_system_keeps_running() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x101] = 0xD3;
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        ..Default::default()
+    };
+    let mut game_boy = GameBoy::with_options("locked.gb", rom, options).unwrap();
+    game_boy.run();
+    let illegal = IllegalOpcode {
+        opcode: 0xD3,
+        address: 0x0101,
+    };
+    assert_eq!(events(&mut game_boy), [GameBoyEvent::CpuLocked { opcode: 0xD3, address: 0x0101 }]);
+    assert_eq!(game_boy.cpu_state(), CpuState::Locked(illegal));
+
+    // A whole frame of scanlines goes by and DIV keeps counting
+    let pc = game_boy.cpu_registers().pc;
+    let mut lines = [false; 154];
+    let mut divider = Vec::new();
+    for _ in 0..FRAME_CYCLES / 4 {
+        game_boy.cpu.cycle();
+        lines[game_boy.cpu.load_8(0xFF44) as usize] = true;
+        divider.push(game_boy.cpu.load_8(0xFF04));
+    }
+    assert!(lines.iter().all(|&seen| seen));
+    divider.dedup();
+    assert!(divider.len() > 255);
+    assert_eq!(game_boy.cpu_registers().pc, pc);
+    assert_eq!(game_boy.cpu_state(), CpuState::Locked(illegal));
+    assert_eq!(events(&mut game_boy), []);
+}
+
+// Latches the MBC7 accelerometer and returns its X and Y readings
+fn latched_tilt(game_boy: &mut GameBoy) -> (u16, u16) {
+    for (address, value) in [(0x0000, 0x0A), (0x4000, 0x40), (0xA000, 0x55), (0xA010, 0xAA)] {
+        game_boy.cpu.store_8(address, value);
+    }
+    let read = |low: u16| u16::from_le_bytes([game_boy.cpu.load_8(low), game_boy.cpu.load_8(low + 0x10)]);
+    (read(0xA020), read(0xA040))
+}
+
+#[test]
+fn tilt_keys_and_analog_input_reach_the_accelerometer() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x147] = 0x22;
+    let options = GameBoyOptions {
+        load_mode: LoadMode::Lenient,
+        ..Default::default()
+    };
+    let mut game_boy = GameBoy::with_options("tilt.gb", rom, options).unwrap();
+    assert_eq!(latched_tilt(&mut game_boy), (0x81D0, 0x81D0));
+
+    game_boy.set_tilt(-0.5, 0.25);
+    assert_eq!(latched_tilt(&mut game_boy), (0x81D0 + 0x38, 0x81D0 + 0x1C));
+    // Keys override the analog tilt while held
+    game_boy.tilt_key_down(TiltDirection::Right);
+    game_boy.tilt_key_down(TiltDirection::Up);
+    assert_eq!(latched_tilt(&mut game_boy), (0x81D0 - 0x70, 0x81D0 - 0x70));
+    game_boy.tilt_key_up(TiltDirection::Right);
+    game_boy.tilt_key_up(TiltDirection::Up);
+    assert_eq!(latched_tilt(&mut game_boy), (0x81D0 + 0x38, 0x81D0 + 0x1C));
+}
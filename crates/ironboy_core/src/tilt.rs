@@ -0,0 +1,75 @@
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum TiltDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// Combines digital (keys) and analog (mouse, sticks) tilt into a single accelerometer reading.
+// X runs from -1.0 (left) to 1.0 (right), Y from -1.0 (away from the player) to 1.0 (towards the player).
+pub struct Tilt {
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    analog: (f32, f32),
+}
+
+impl Default for Tilt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tilt {
+    pub fn new() -> Self {
+        Tilt {
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            analog: (0.0, 0.0),
+        }
+    }
+
+    pub fn key_down(&mut self, direction: TiltDirection) {
+        self.set_key(direction, true);
+    }
+
+    pub fn key_up(&mut self, direction: TiltDirection) {
+        self.set_key(direction, false);
+    }
+
+    fn set_key(&mut self, direction: TiltDirection, pressed: bool) {
+        match direction {
+            TiltDirection::Left => self.left = pressed,
+            TiltDirection::Right => self.right = pressed,
+            TiltDirection::Up => self.up = pressed,
+            TiltDirection::Down => self.down = pressed,
+        }
+    }
+
+    pub fn set_analog(&mut self, x: f32, y: f32) {
+        self.analog = (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0));
+    }
+
+    // Pointer position relative to the centre of an area, e.g. the mouse inside the window
+    pub fn set_pointer(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let x = (x as f32 / width as f32) * 2.0 - 1.0;
+        let y = (y as f32 / height as f32) * 2.0 - 1.0;
+        self.set_analog(x, y);
+    }
+
+    pub fn value(&self) -> (f32, f32) {
+        if self.left || self.right || self.up || self.down {
+            let x = self.right as i8 - self.left as i8;
+            let y = self.down as i8 - self.up as i8;
+            return (x as f32, y as f32);
+        }
+        self.analog
+    }
+}
@@ -1,6 +1,6 @@
 use crate::Cpu;
 
-use ironboy_common::MemoryInterface;
+use ironboy_common::{MemoryInterface, OamAccess};
 
 use super::{R8, R16};
 
@@ -253,8 +253,9 @@ pub fn cp_a_imm8<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 pub fn inc_r16<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let operand = (cpu.current_opcode & 0b0011_0000) >> 4;
     let register = R16::from(operand);
-    let value = register.load(cpu).wrapping_add(1);
-    register.store(cpu, value);
+    let value = register.load(cpu);
+    cpu.bus.corrupt_oam(value, OamAccess::Write);
+    register.store(cpu, value.wrapping_add(1));
     8
 }
 
@@ -274,8 +275,9 @@ pub fn inc_r8<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 pub fn dec_r16<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let operand = (cpu.current_opcode & 0b0011_0000) >> 4;
     let register = R16::from(operand);
-    let value = register.load(cpu).wrapping_sub(1);
-    register.store(cpu, value);
+    let value = register.load(cpu);
+    cpu.bus.corrupt_oam(value, OamAccess::Write);
+    register.store(cpu, value.wrapping_sub(1));
     8
 }
 
@@ -14,14 +14,14 @@ pub fn ld_r16_imm16<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 pub fn ld_r16mem_a<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let destination = (cpu.current_opcode & 0b0011_0000) >> 4;
     let address = R16Memory::from(destination).load(cpu);
-    cpu.store_8(address, cpu.registers.a);
+    cpu.write_memory(address, cpu.registers.a);
     8
 }
 
 pub fn ld_a_r16mem<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let source = (cpu.current_opcode & 0b0011_0000) >> 4;
     let address = R16Memory::from(source).load(cpu);
-    cpu.registers.a = cpu.load_8(address);
+    cpu.registers.a = cpu.read_memory(address);
     8
 }
 
@@ -64,7 +64,7 @@ pub fn ld_imm8mem_a<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 
 pub fn ld_imm16mem_a<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let address = cpu.fetch_word();
-    cpu.store_8(address, cpu.registers.a);
+    cpu.write_memory(address, cpu.registers.a);
     16
 }
 
@@ -82,7 +82,7 @@ pub fn ld_a_imm8mem<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 
 pub fn ld_a_imm16mem<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
     let address = cpu.fetch_word();
-    cpu.registers.a = cpu.load_8(address);
+    cpu.registers.a = cpu.read_memory(address);
     16
 }
 
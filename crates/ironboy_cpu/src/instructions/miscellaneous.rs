@@ -1,4 +1,4 @@
-use crate::Cpu;
+use crate::{Cpu, SPEED_SWITCH_M_CYCLES};
 
 use ironboy_common::MemoryInterface;
 
@@ -54,12 +54,38 @@ pub fn ccf<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
 }
 
 pub fn stop<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
-    cpu.bus.change_speed();
+    if cpu.bus.change_speed() {
+        cpu.fetch_byte();
+        cpu.speed_switch_cycles = SPEED_SWITCH_M_CYCLES;
+        return 4;
+    }
+
+    // A held button keeps the system from entering STOP mode, it halts instead unless an interrupt is pending
+    if cpu.bus.joypad_active() {
+        if cpu.requested_interrupts() == 0 {
+            cpu.halted = true;
+        }
+        return 4;
+    }
+
+    cpu.fetch_byte();
+    cpu.stopped = true;
+    cpu.bus.set_stopped(true);
     4
 }
 
 pub fn halt<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
-    cpu.halted = true;
+    if cpu.interrupts.ime() || cpu.requested_interrupts() == 0 {
+        cpu.halted = true;
+        return 4;
+    }
+
+    if cpu.interrupts.ei_pending() {
+        // The pending interrupt returns to the HALT, which then runs again
+        cpu.registers.pc = cpu.registers.pc.wrapping_sub(1);
+    } else {
+        cpu.halt_bug = true;
+    }
     4
 }
 
@@ -80,7 +106,7 @@ pub fn prefix<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
         0b01 => bit_operations::bit_b3_r8(cpu, opcode),
         0b10 => bit_operations::res_b3_r8(cpu, opcode),
         0b11 => bit_operations::set_b3_r8(cpu, opcode),
-        0b00 => {
+        _ => {
             let operation = (opcode & 0b0011_1000) >> 3;
             match operation {
                 0b000 => rotate_shift::rlc_r8(cpu, opcode),
@@ -90,10 +116,8 @@ pub fn prefix<I: MemoryInterface>(cpu: &mut Cpu<I>) -> u8 {
                 0b100 => rotate_shift::sla_r8(cpu, opcode),
                 0b101 => rotate_shift::sra_r8(cpu, opcode),
                 0b110 => rotate_shift::swap_r8(cpu, opcode),
-                0b111 => rotate_shift::srl_r8(cpu, opcode),
-                _ => panic!("No operation exists"),
+                _ => rotate_shift::srl_r8(cpu, opcode),
             }
         }
-        _ => panic!("No operation exists"),
     }
 }
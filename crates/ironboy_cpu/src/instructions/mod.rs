@@ -10,6 +10,7 @@ pub mod rotate_shift;
 #[derive(Debug, PartialEq)]
 pub enum Instruction {
     None,
+    Illegal,
     Nop,
     LdR16Imm16,
     LdR16MemA,
@@ -141,7 +142,8 @@ impl From<u8> for Instruction {
             0xDE => Instruction::SbcAImm8,
             0xEE => Instruction::XorAImm8,
             0xFE => Instruction::CpAImm8,
-            code => panic!("Code {:#04X} not implemented", code),
+            // 0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC and 0xFD
+            _ => Instruction::Illegal,
         }
     }
 }
@@ -384,6 +386,7 @@ impl Instruction {
                 }
             }
             Instruction::None => "Instruction not implemented".to_string(),
+            Instruction::Illegal => format!("ILLEGAL {:#04X}", opcode),
         }
     }
 }
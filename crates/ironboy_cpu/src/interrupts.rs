@@ -36,6 +36,11 @@ impl Interrupts {
         self.enable_interrupt = self.enable_interrupt.saturating_sub(1);
     }
 
+    // EI was executed but IME is not set yet
+    pub fn ei_pending(&self) -> bool {
+        self.enable_interrupt > 0
+    }
+
     pub fn set_ei(&mut self) {
         self.enable_interrupt = 2
     }
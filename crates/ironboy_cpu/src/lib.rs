@@ -2,7 +2,7 @@ use std::{fs::OpenOptions, io::Write};
 
 use instructions::{arithmetic_logic, branch, load, miscellaneous, rotate_shift};
 use interrupts::{Interrupts, IE_ADDRESS, IF_ADDRESS};
-use ironboy_common::MemoryInterface;
+use ironboy_common::{MemoryInterface, OamAccess};
 
 use self::{instructions::Instruction, registers::Registers};
 
@@ -12,6 +12,26 @@ mod operands;
 pub mod registers;
 mod tests;
 
+// M-cycles the CPU idles after a CGB speed switch while the clock settles
+const SPEED_SWITCH_M_CYCLES: u32 = 2050;
+
+// Opcode that locked up the CPU and the address it was fetched from
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct IllegalOpcode {
+    pub opcode: u8,
+    pub address: u16,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CpuState {
+    Running,
+    Halted,
+    // Low-power mode entered by STOP, woken up by the joypad
+    Stopped,
+    // Stuck on an illegal opcode until power-off, the rest of the system keeps running
+    Locked(IllegalOpcode),
+}
+
 pub struct Cpu<I: MemoryInterface> {
     pub bus: I,
     registers: Registers,
@@ -19,6 +39,12 @@ pub struct Cpu<I: MemoryInterface> {
     current_opcode: u8,
     current_instruction: Instruction,
     halted: bool,
+    halt_bug: bool,
+    stopped: bool,
+    speed_switch_cycles: u32,
+    // Cycles already run on the bus by the current step
+    ticked_cycles: u32,
+    locked: Option<IllegalOpcode>,
     debugging: bool,
     total_cycles: u32,
 }
@@ -36,8 +62,20 @@ impl<I: MemoryInterface> MemoryInterface for Cpu<I> {
         self.bus.cycle(cycles, halted)
     }
 
-    fn change_speed(&mut self) {
-        self.bus.change_speed();
+    fn change_speed(&mut self) -> bool {
+        self.bus.change_speed()
+    }
+
+    fn set_stopped(&mut self, stopped: bool) {
+        self.bus.set_stopped(stopped);
+    }
+
+    fn joypad_active(&self) -> bool {
+        self.bus.joypad_active()
+    }
+
+    fn corrupt_oam(&mut self, address: u16, access: OamAccess) {
+        self.bus.corrupt_oam(address, access);
     }
 }
 
@@ -50,19 +88,62 @@ impl<I: MemoryInterface> Cpu<I> {
             current_opcode: 0x00,
             current_instruction: Instruction::None,
             halted: false,
+            halt_bug: false,
+            stopped: false,
+            speed_switch_cycles: 0,
+            ticked_cycles: 0,
+            locked: None,
             debugging: false,
             total_cycles: 0,
         }
     }
 
+    // Memory an instruction reads or writes, unlike fetches and interrupt dispatch these can trigger the OAM bug
+    pub(crate) fn read_memory(&mut self, address: u16) -> u8 {
+        self.bus.corrupt_oam(address, OamAccess::Read);
+        self.load_8(address)
+    }
+
+    pub(crate) fn write_memory(&mut self, address: u16, value: u8) {
+        self.bus.corrupt_oam(address, OamAccess::Write);
+        self.store_8(address, value)
+    }
+
     pub fn cycle(&mut self) -> u32 {
         let cpu_cycles = self.cpu_cycle();
-        let cycles = self.bus.cycle(cpu_cycles, self.halted);
+        let remaining_cycles = cpu_cycles.saturating_sub(self.ticked_cycles);
+        let mut cycles = std::mem::take(&mut self.ticked_cycles);
+        if remaining_cycles > 0 {
+            cycles += self.bus.cycle(remaining_cycles, self.halted);
+        }
         self.total_cycles += cycles;
         cycles
     }
 
+    // Runs the rest of the system for one M-cycle in the middle of a step
+    fn tick(&mut self) {
+        self.ticked_cycles += self.bus.cycle(4, false);
+    }
+
     fn cpu_cycle(&mut self) -> u32 {
+        // A locked CPU ignores interrupts and never fetches again, the rest of the system keeps running
+        if self.locked.is_some() {
+            return 4;
+        }
+
+        if self.speed_switch_cycles > 0 {
+            self.speed_switch_cycles -= 1;
+            return 4;
+        }
+
+        if self.stopped {
+            if !self.joypad_active() {
+                return 4;
+            }
+            self.stopped = false;
+            self.set_stopped(false);
+        }
+
         let interrupt_cycles = self.execute_interrupt() as u32;
         if interrupt_cycles != 0 {
             return interrupt_cycles;
@@ -86,10 +167,7 @@ impl<I: MemoryInterface> Cpu<I> {
             return 0;
         }
 
-        let mut interrupt_flag = self.load_8(IF_ADDRESS);
-        let interrupt_enable = self.load_8(IE_ADDRESS);
-        let requested_interrupt = interrupt_flag & interrupt_enable;
-        if requested_interrupt == 0 {
+        if self.requested_interrupts() == 0 {
             return 0;
         }
 
@@ -98,23 +176,49 @@ impl<I: MemoryInterface> Cpu<I> {
             return 0;
         }
         self.interrupts.set_ime(false);
-        let interrupt = requested_interrupt.trailing_zeros();
-        if interrupt >= 5 {
-            panic!("Invalid interrupt triggered");
-        }
 
-        interrupt_flag &= !(1 << interrupt);
-        self.store_8(IF_ADDRESS, interrupt_flag);
+        // Two idle M-cycles before the return address is pushed
+        self.tick();
+        self.tick();
 
         let address = self.registers.pc;
-        self.push_stack(address);
-        self.registers.pc = 0x0040 | ((interrupt as u16) << 3);
-        16
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.store_8(self.registers.sp, (address >> 8) as u8);
+        self.tick();
+
+        // The interrupt is only picked after the high byte push, which may have overwritten IE.
+        // With nothing left to service the dispatch is cancelled and jumps to 0x0000.
+        let requested_interrupt = self.requested_interrupts();
+        let vector = match requested_interrupt {
+            0 => 0x0000,
+            _ => {
+                let interrupt = requested_interrupt.trailing_zeros();
+                let interrupt_flag = self.load_8(IF_ADDRESS) & !(1 << interrupt);
+                self.store_8(IF_ADDRESS, interrupt_flag);
+                0x0040 | ((interrupt as u16) << 3)
+            }
+        };
+
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.store_8(self.registers.sp, address as u8);
+        self.tick();
+
+        self.registers.pc = vector;
+        20
+    }
+
+    fn requested_interrupts(&self) -> u8 {
+        self.load_8(IF_ADDRESS) & self.load_8(IE_ADDRESS) & 0x1F
     }
 
     fn fetch_instruction(&mut self) {
         self.current_opcode = self.load_8(self.registers.pc);
-        self.registers.pc = self.registers.pc.wrapping_add(1);
+        // The HALT bug reads the byte after HALT twice
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.pc = self.registers.pc.wrapping_add(1);
+        }
         self.current_instruction = Instruction::from(self.current_opcode)
     }
 
@@ -130,6 +234,8 @@ impl<I: MemoryInterface> Cpu<I> {
         word
     }
 
+    // Stack accesses with SP in OAM don't trigger the OAM bug yet, they mix increments and decrements with the
+    // reads and writes in patterns of their own
     fn pop_stack(&mut self) -> u16 {
         let value = self.load_16(self.registers.sp);
         self.registers.sp = self.registers.sp.wrapping_add(2);
@@ -205,11 +311,43 @@ impl<I: MemoryInterface> Cpu<I> {
             Instruction::Prefix => miscellaneous::prefix(self),
             Instruction::Di => miscellaneous::di(self),
             Instruction::Ei => miscellaneous::ei(self),
-            Instruction::Nop => 4,
-            Instruction::None => panic!("Instruction not implemented"),
+            Instruction::Nop | Instruction::None => 4,
+            Instruction::Illegal => {
+                self.locked = Some(IllegalOpcode {
+                    opcode: self.current_opcode,
+                    address: self.registers.pc.wrapping_sub(1),
+                });
+                4
+            }
         }
     }
 
+    pub fn locked(&self) -> Option<IllegalOpcode> {
+        self.locked
+    }
+
+    pub fn state(&self) -> CpuState {
+        match self.locked {
+            Some(illegal) => CpuState::Locked(illegal),
+            None if self.stopped => CpuState::Stopped,
+            None if self.halted => CpuState::Halted,
+            None => CpuState::Running,
+        }
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn total_cycles(&self) -> u32 {
+        self.total_cycles
+    }
+
+    // Appends every executed instruction to iron_boy.txt
+    pub fn set_debugging(&mut self, debugging: bool) {
+        self.debugging = debugging;
+    }
+
     fn log_cycle(&self, pc: u16) {
         let flags = format!(
             "{}{}{}{}",
@@ -236,12 +374,9 @@ impl<I: MemoryInterface> Cpu<I> {
             self.registers.sp,
         );
 
-        let mut file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open("iron_boy.txt")
-            .expect("Could not open file");
-        file.write(log.as_bytes()).expect("Could not write file");
+        // Logging is best effort, a failed write should never stop emulation
+        if let Ok(mut file) = OpenOptions::new().create(true).read(true).append(true).open("iron_boy.txt") {
+            let _ = file.write_all(log.as_bytes());
+        }
     }
 }
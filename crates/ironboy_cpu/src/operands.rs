@@ -1,6 +1,6 @@
 use std::fmt;
 
-use ironboy_common::MemoryInterface;
+use ironboy_common::{MemoryInterface, OamAccess};
 
 use super::Cpu;
 
@@ -18,7 +18,7 @@ pub enum R8 {
 
 impl From<u8> for R8 {
     fn from(value: u8) -> Self {
-        match value {
+        match value & 0b111 {
             0b000 => R8::B,
             0b001 => R8::C,
             0b010 => R8::D,
@@ -26,8 +26,7 @@ impl From<u8> for R8 {
             0b100 => R8::H,
             0b101 => R8::L,
             0b110 => R8::HLMem,
-            0b111 => R8::A,
-            _ => panic!("Invalid value was passed"),
+            _ => R8::A,
         }
     }
 }
@@ -48,7 +47,7 @@ impl fmt::Display for R8 {
 }
 
 impl R8 {
-    pub fn load<I: MemoryInterface>(&self, cpu: &Cpu<I>) -> u8 {
+    pub fn load<I: MemoryInterface>(&self, cpu: &mut Cpu<I>) -> u8 {
         match self {
             R8::A => cpu.registers.a,
             R8::B => cpu.registers.b,
@@ -57,7 +56,7 @@ impl R8 {
             R8::E => cpu.registers.e,
             R8::H => cpu.registers.h,
             R8::L => cpu.registers.l,
-            R8::HLMem => cpu.load_8(cpu.registers.hl()),
+            R8::HLMem => cpu.read_memory(cpu.registers.hl()),
         }
     }
 
@@ -70,7 +69,7 @@ impl R8 {
             R8::E => cpu.registers.e = value,
             R8::H => cpu.registers.h = value,
             R8::L => cpu.registers.l = value,
-            R8::HLMem => cpu.store_8(cpu.registers.hl(), value),
+            R8::HLMem => cpu.write_memory(cpu.registers.hl(), value),
         }
     }
 }
@@ -85,12 +84,11 @@ pub enum R16 {
 
 impl From<u8> for R16 {
     fn from(value: u8) -> R16 {
-        match value {
+        match value & 0b11 {
             0b00 => R16::BC,
             0b01 => R16::DE,
             0b10 => R16::HL,
-            0b11 => R16::SP,
-            _ => panic!("Invalid value was passed"),
+            _ => R16::SP,
         }
     }
 }
@@ -136,12 +134,11 @@ pub enum R16Stack {
 
 impl From<u8> for R16Stack {
     fn from(value: u8) -> R16Stack {
-        match value {
+        match value & 0b11 {
             0b00 => R16Stack::BC,
             0b01 => R16Stack::DE,
             0b10 => R16Stack::HL,
-            0b11 => R16Stack::AF,
-            _ => panic!("Invalid value was passed"),
+            _ => R16Stack::AF,
         }
     }
 }
@@ -187,12 +184,11 @@ pub enum R16Memory {
 
 impl From<u8> for R16Memory {
     fn from(value: u8) -> R16Memory {
-        match value {
+        match value & 0b11 {
             0b00 => R16Memory::BC,
             0b01 => R16Memory::DE,
             0b10 => R16Memory::HLI,
-            0b11 => R16Memory::HLD,
-            _ => panic!("Invalid value was passed"),
+            _ => R16Memory::HLD,
         }
     }
 }
@@ -213,8 +209,16 @@ impl R16Memory {
         match self {
             R16Memory::BC => cpu.registers.bc(),
             R16Memory::DE => cpu.registers.de(),
-            R16Memory::HLI => cpu.registers.increment_hl(),
-            R16Memory::HLD => cpu.registers.decrement_hl(),
+            R16Memory::HLI => {
+                let address = cpu.registers.increment_hl();
+                cpu.bus.corrupt_oam(address, OamAccess::Write);
+                address
+            }
+            R16Memory::HLD => {
+                let address = cpu.registers.decrement_hl();
+                cpu.bus.corrupt_oam(address, OamAccess::Write);
+                address
+            }
         }
     }
 }
@@ -229,12 +233,11 @@ pub enum Condition {
 
 impl From<u8> for Condition {
     fn from(value: u8) -> Condition {
-        match value {
+        match value & 0b11 {
             0b000 => Condition::NZ,
             0b001 => Condition::Z,
             0b010 => Condition::NC,
-            0b011 => Condition::C,
-            _ => panic!("Invalid value was passed"),
+            _ => Condition::C,
         }
     }
 }
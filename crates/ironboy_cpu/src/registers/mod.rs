@@ -1,4 +1,4 @@
-use ironboy_common::GameBoyMode;
+use ironboy_common::{GameBoyMode, Model};
 use flags::Flags;
 
 pub mod flags;
@@ -32,6 +32,18 @@ impl Registers {
                 pc: 0x0100,
                 sp: 0xFFFE,
             },
+            GameBoyMode::SuperGameBoy => Registers {
+                a: 0x01,
+                f: Flags::from(0b0000_0000),
+                b: 0x00,
+                c: 0x14,
+                d: 0x00,
+                e: 0x00,
+                h: 0xC0,
+                l: 0x60,
+                pc: 0x0100,
+                sp: 0xFFFE,
+            },
             GameBoyMode::Color => Registers {
                 a: 0x11,
                 f: Flags::from(0b1000_0000),
@@ -59,6 +71,22 @@ impl Registers {
         }
     }
 
+    // State left behind by each model's boot ROM
+    pub fn with_model(model: Model, mode: GameBoyMode) -> Self {
+        let mut registers = Registers::new(mode);
+        match model {
+            // The MGB and SGB2 boot ROMs identify themselves with A = 0xFF
+            Model::Mgb | Model::Sgb2 => registers.a = 0xFF,
+            // The AGB boot ROM runs an extra INC B, which also clears Z and H
+            Model::Agb => {
+                registers.b = registers.b.wrapping_add(1);
+                registers.f = Flags::from(0b0000_0000);
+            }
+            Model::Dmg | Model::Sgb | Model::Cgb => {}
+        }
+        registers
+    }
+
     pub fn af(&self) -> u16 {
         (self.a as u16) << 8 | u8::from(&self.f) as u16
     }
@@ -97,13 +125,13 @@ impl Registers {
 
     pub fn decrement_hl(&mut self) -> u16 {
         let hl = self.hl();
-        self.set_hl(hl - 1);
+        self.set_hl(hl.wrapping_sub(1));
         hl
     }
 
     pub fn increment_hl(&mut self) -> u16 {
         let hl = self.hl();
-        self.set_hl(hl + 1);
+        self.set_hl(hl.wrapping_add(1));
         hl
     }
 }
@@ -2,19 +2,31 @@
 mod tests {
     use std::fs::{self};
 
-    use ironboy_common::{GameBoyMode, MemoryInterface};
+    use ironboy_common::{GameBoyMode, MemoryInterface, Model};
     use serde::{Deserialize, Serialize};
 
-    use crate::{registers::Registers, Cpu};
+    use crate::{registers::Registers, Cpu, CpuState, IllegalOpcode, SPEED_SWITCH_M_CYCLES};
+
+    const ILLEGAL_OPCODES: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
 
     pub struct SimpleBus {
         data: Vec<u8>,
+        cycles: u32,
+        speed_switch_armed: bool,
+        joypad_active: bool,
+        stopped: bool,
     }
 
     impl SimpleBus {
         #[allow(dead_code)]
         pub fn new() -> SimpleBus {
-            SimpleBus { data: vec![0; 0x10000] }
+            SimpleBus {
+                data: vec![0; 0x10000],
+                cycles: 0,
+                speed_switch_armed: false,
+                joypad_active: false,
+                stopped: false,
+            }
         }
     }
 
@@ -28,10 +40,23 @@ mod tests {
         }
 
         fn cycle(&mut self, cycles: u32, _cpu_halted: bool) -> u32 {
+            self.cycles += cycles;
             cycles
         }
 
-        fn change_speed(&mut self) {}
+        fn change_speed(&mut self) -> bool {
+            let armed = self.speed_switch_armed;
+            self.speed_switch_armed = false;
+            armed
+        }
+
+        fn set_stopped(&mut self, stopped: bool) {
+            self.stopped = stopped;
+        }
+
+        fn joypad_active(&self) -> bool {
+            self.joypad_active
+        }
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -115,4 +140,210 @@ mod tests {
             }
         }
     }
+
+    fn locked_cpu(opcode: u8) -> Cpu<SimpleBus> {
+        let mut cpu = Cpu::new(SimpleBus::new(), Registers::new(GameBoyMode::Color));
+        cpu.bus.store_8(0x0100, 0x00);
+        cpu.bus.store_8(0x0101, opcode);
+        cpu.bus.store_8(0x0102, 0x3C);
+        cpu.cycle();
+        cpu.cycle();
+        cpu
+    }
+
+    #[test]
+    fn illegal_opcodes_lock_the_cpu() {
+        for opcode in ILLEGAL_OPCODES {
+            let cpu = locked_cpu(opcode);
+            assert_eq!(cpu.state(), CpuState::Locked(IllegalOpcode { opcode, address: 0x0101 }));
+            assert_eq!(cpu.registers.pc, 0x0102);
+        }
+    }
+
+    #[test]
+    fn locked_cpu_stops_fetching_but_keeps_the_bus_running() {
+        let mut cpu = locked_cpu(0xD3);
+        let a = cpu.registers.a;
+        let bus_cycles = cpu.bus.cycles;
+        for _ in 0..100 {
+            assert_eq!(cpu.cycle(), 4);
+        }
+        assert_eq!(cpu.registers.pc, 0x0102);
+        assert_eq!(cpu.registers.a, a);
+        assert_eq!(cpu.bus.cycles, bus_cycles + 400);
+    }
+
+    #[test]
+    fn locked_cpu_ignores_interrupts() {
+        let mut cpu = locked_cpu(0xFC);
+        cpu.interrupts.set_ime(true);
+        cpu.bus.store_8(0xFFFF, 0x1F);
+        cpu.bus.store_8(0xFF0F, 0x1F);
+        let sp = cpu.registers.sp;
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0102);
+        assert_eq!(cpu.registers.sp, sp);
+        assert_eq!(cpu.bus.load_8(0xFF0F), 0x1F);
+    }
+
+    fn cpu_with_program(program: &[u8]) -> Cpu<SimpleBus> {
+        let mut cpu = Cpu::new(SimpleBus::new(), Registers::new(GameBoyMode::Color));
+        for (offset, byte) in program.iter().enumerate() {
+            cpu.bus.store_8(0x0100 + offset as u16, *byte);
+        }
+        cpu
+    }
+
+    #[test]
+    fn halt_bug_reads_the_next_byte_twice() {
+        // HALT, INC A, NOP with IME off and a pending interrupt
+        let mut cpu = cpu_with_program(&[0x76, 0x3C, 0x00]);
+        cpu.registers.a = 0;
+        cpu.bus.store_8(0xFFFF, 0x01);
+        cpu.bus.store_8(0xFF0F, 0x01);
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Running);
+        cpu.cycle();
+        cpu.cycle();
+        assert_eq!(cpu.registers.a, 2);
+        assert_eq!(cpu.registers.pc, 0x0102);
+    }
+
+    #[test]
+    fn halt_without_pending_interrupt_halts() {
+        let mut cpu = cpu_with_program(&[0x76]);
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Halted);
+        assert_eq!(cpu.registers.pc, 0x0101);
+    }
+
+    #[test]
+    fn ei_then_halt_returns_to_the_halt() {
+        let mut cpu = cpu_with_program(&[0xFB, 0x76]);
+        cpu.bus.store_8(0xFFFF, 0x01);
+        cpu.bus.store_8(0xFF0F, 0x01);
+        cpu.cycle();
+        cpu.cycle();
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0040);
+        assert_eq!(cpu.load_16(cpu.registers.sp), 0x0101);
+    }
+
+    #[test]
+    fn stop_waits_for_the_joypad() {
+        let mut cpu = cpu_with_program(&[0x10, 0x00, 0x3C]);
+        cpu.registers.a = 0;
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Stopped);
+        assert!(cpu.bus.stopped);
+        for _ in 0..10 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.registers.a, 0);
+
+        cpu.bus.joypad_active = true;
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Running);
+        assert!(!cpu.bus.stopped);
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    #[test]
+    fn stop_with_held_button_does_not_stop() {
+        let mut cpu = cpu_with_program(&[0x10, 0x3C]);
+        cpu.bus.joypad_active = true;
+        cpu.bus.store_8(0xFFFF, 0x10);
+        cpu.bus.store_8(0xFF0F, 0x10);
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Running);
+        assert!(!cpu.bus.stopped);
+        assert_eq!(cpu.registers.pc, 0x0101);
+    }
+
+    #[test]
+    fn stop_with_held_button_and_no_interrupt_halts() {
+        let mut cpu = cpu_with_program(&[0x10, 0x3C]);
+        cpu.bus.joypad_active = true;
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Halted);
+        // DIV is only reset when the bus enters STOP mode
+        assert!(!cpu.bus.stopped);
+        assert_eq!(cpu.registers.pc, 0x0101);
+    }
+
+    #[test]
+    fn speed_switch_pauses_the_cpu() {
+        let mut cpu = cpu_with_program(&[0x10, 0x00, 0x3C]);
+        cpu.registers.a = 0;
+        cpu.bus.speed_switch_armed = true;
+        cpu.cycle();
+        assert_eq!(cpu.state(), CpuState::Running);
+        for _ in 0..SPEED_SWITCH_M_CYCLES {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.registers.a, 0);
+        cpu.cycle();
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    fn cpu_with_pending_interrupt(pc: u16, sp: u16, interrupt_enable: u8, interrupt_flag: u8) -> Cpu<SimpleBus> {
+        let mut cpu = Cpu::new(SimpleBus::new(), Registers::new(GameBoyMode::Color));
+        cpu.registers.pc = pc;
+        cpu.registers.sp = sp;
+        cpu.interrupts.set_ime(true);
+        cpu.bus.store_8(0xFFFF, interrupt_enable);
+        cpu.bus.store_8(0xFF0F, interrupt_flag);
+        cpu
+    }
+
+    #[test]
+    fn interrupt_dispatch_takes_five_m_cycles() {
+        let mut cpu = cpu_with_pending_interrupt(0x1234, 0xD000, 0x04, 0x04);
+        assert_eq!(cpu.cycle(), 20);
+        assert_eq!(cpu.bus.cycles, 20);
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.registers.sp, 0xCFFE);
+        assert_eq!(cpu.load_16(0xCFFE), 0x1234);
+        assert_eq!(cpu.bus.load_8(0xFF0F), 0x00);
+        assert!(!cpu.interrupts.ime());
+    }
+
+    #[test]
+    fn ie_push_cancels_dispatch() {
+        // The high byte of PC lands on IE and disables the only requested interrupt
+        let mut cpu = cpu_with_pending_interrupt(0x0234, 0x0000, 0x01, 0x01);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0000);
+        assert_eq!(cpu.registers.sp, 0xFFFE);
+        assert_eq!(cpu.bus.load_8(0xFFFF), 0x02);
+        assert_eq!(cpu.bus.load_8(0xFF0F), 0x01);
+    }
+
+    #[test]
+    fn ie_push_redirects_dispatch() {
+        // The high byte of PC enables the timer interrupt instead of VBlank
+        let mut cpu = cpu_with_pending_interrupt(0x0434, 0x0000, 0x01, 0x05);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.bus.load_8(0xFF0F), 0x01);
+    }
+
+    #[test]
+    fn upper_interrupt_bits_are_ignored() {
+        let mut cpu = cpu_with_pending_interrupt(0x1234, 0xD000, 0xE0, 0xE0);
+        assert_eq!(cpu.cycle(), 4);
+        assert_eq!(cpu.registers.sp, 0xD000);
+    }
+
+    #[test]
+    fn boot_registers_depend_on_the_model() {
+        let agb = Registers::with_model(Model::Agb, GameBoyMode::Color);
+        assert_eq!((agb.a, agb.b, u8::from(&agb.f)), (0x11, 0x01, 0x00));
+
+        let mgb = Registers::with_model(Model::Mgb, GameBoyMode::Monochrome);
+        assert_eq!((mgb.a, u8::from(&mgb.f)), (0xFF, 0xB0));
+
+        let sgb2 = Registers::with_model(Model::Sgb2, GameBoyMode::SuperGameBoy);
+        assert_eq!((sgb2.a, sgb2.c, sgb2.hl()), (0xFF, 0x14, 0xC060));
+    }
 }
@@ -1,5 +1,16 @@
+use std::str::FromStr;
+
 use ironboy_common::SystemMemoryAccess;
+use sgb::PacketReceiver;
+
+mod sgb;
+mod socd;
+#[cfg(test)]
+mod tests;
 
+pub use socd::SocdPolicy;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum JoypadButton {
     Right,
     Left,
@@ -11,10 +22,56 @@ pub enum JoypadButton {
     Start,
 }
 
+impl JoypadButton {
+    pub const ALL: [JoypadButton; 8] = [
+        JoypadButton::Right,
+        JoypadButton::Left,
+        JoypadButton::Up,
+        JoypadButton::Down,
+        JoypadButton::A,
+        JoypadButton::B,
+        JoypadButton::Select,
+        JoypadButton::Start,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JoypadButton::Right => "right",
+            JoypadButton::Left => "left",
+            JoypadButton::Up => "up",
+            JoypadButton::Down => "down",
+            JoypadButton::A => "a",
+            JoypadButton::B => "b",
+            JoypadButton::Select => "select",
+            JoypadButton::Start => "start",
+        }
+    }
+}
+
+impl FromStr for JoypadButton {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        JoypadButton::ALL
+            .into_iter()
+            .find(|button| button.name() == s)
+            .ok_or_else(|| format!("unknown button {s}"))
+    }
+}
+
 pub struct JoyPad {
+    // Directions as the game sees them after the SOCD policy, active low like the rows of P1
     row0: u8,
     row1: u8,
+    // Directions the player holds
+    directions: u8,
+    right_last: bool,
+    down_last: bool,
+    socd: SocdPolicy,
     value: u8,
+    sgb: Option<PacketReceiver>,
+    sgb_command: Option<Vec<u8>>,
     pub interrupt: u8,
 }
 
@@ -25,6 +82,9 @@ impl SystemMemoryAccess for JoyPad {
 
     fn write_8(&mut self, _: u16, value: u8) {
         self.value = (self.value & 0xCF) | (value & 0x30);
+        if let Some(command) = self.sgb.as_mut().and_then(|sgb| sgb.write(value & 0x30)) {
+            self.sgb_command = Some(command);
+        }
         self.update_buttons();
     }
 }
@@ -34,54 +94,113 @@ impl JoyPad {
         JoyPad {
             row0: 0x0F,
             row1: 0x0F,
+            directions: 0x0F,
+            right_last: false,
+            down_last: false,
+            socd: SocdPolicy::default(),
             value: 0xFF,
+            sgb: None,
+            sgb_command: None,
             interrupt: 0,
         }
     }
 
+    // Decodes SGB command packets from P1 writes, MLT_REQ is handled here and the rest is handed out
+    pub fn enable_sgb(&mut self) {
+        self.sgb = Some(PacketReceiver::new());
+    }
+
+    pub fn take_sgb_command(&mut self) -> Option<Vec<u8>> {
+        self.sgb_command.take()
+    }
+
+    pub fn set_socd_policy(&mut self, policy: SocdPolicy) {
+        self.socd = policy;
+        self.resolve_directions();
+    }
+
+    pub fn socd_policy(&self) -> SocdPolicy {
+        self.socd
+    }
+
+    fn resolve_directions(&mut self) {
+        let held = |bit: u8| self.directions & (1 << bit) == 0;
+        let (left, right) = self.socd.resolve(held(1), held(0), self.right_last);
+        let (up, down) = self.socd.resolve(held(2), held(3), self.down_last);
+        let pressed = right as u8 | (left as u8) << 1 | (up as u8) << 2 | (down as u8) << 3;
+        self.row0 = !pressed & 0x0F;
+        self.update_buttons();
+    }
+
     fn update_buttons(&mut self) {
         let values = self.value & 0xF;
         let mut updated_values = 0xF;
+        // Only the first SGB controller has buttons connected
+        let player = self.sgb.as_ref().map_or(0, |sgb| sgb.current_player());
 
-        if self.value & 0x10 == 0x00 {
+        if self.value & 0x10 == 0x00 && player == 0 {
             updated_values &= self.row0;
         }
-        if self.value & 0x20 == 0x00 {
+        if self.value & 0x20 == 0x00 && player == 0 {
             updated_values &= self.row1;
         }
+        // With MLT_REQ active the ID of the selected controller is read with both rows deselected
+        let multiplayer_id = self.value & 0x30 == 0x30 && self.sgb.as_ref().is_some_and(|sgb| sgb.multiplayer());
+        if multiplayer_id {
+            updated_values = 0xF - player;
+        }
 
-        if values == 0xF && updated_values != 0xF {
+        // Requested by any input line falling from high to low, whether a button was pressed or its row got selected.
+        // The controller ID isn't driven by buttons and never requests it.
+        if !multiplayer_id && values & !updated_values != 0 {
             self.interrupt |= 0x10;
         }
 
         self.value = (self.value & 0xF0) | updated_values;
     }
 
+    // True when a button in a selected row is held
+    pub fn any_pressed(&self) -> bool {
+        self.value & 0x0F != 0x0F
+    }
+
     pub fn button_down(&mut self, button: JoypadButton) {
         match button {
-            JoypadButton::Right => self.row0 &= !(1 << 0),
-            JoypadButton::Left => self.row0 &= !(1 << 1),
-            JoypadButton::Up => self.row0 &= !(1 << 2),
-            JoypadButton::Down => self.row0 &= !(1 << 3),
+            JoypadButton::Right => {
+                self.directions &= !(1 << 0);
+                self.right_last = true;
+            }
+            JoypadButton::Left => {
+                self.directions &= !(1 << 1);
+                self.right_last = false;
+            }
+            JoypadButton::Up => {
+                self.directions &= !(1 << 2);
+                self.down_last = false;
+            }
+            JoypadButton::Down => {
+                self.directions &= !(1 << 3);
+                self.down_last = true;
+            }
             JoypadButton::A => self.row1 &= !(1 << 0),
             JoypadButton::B => self.row1 &= !(1 << 1),
             JoypadButton::Select => self.row1 &= !(1 << 2),
             JoypadButton::Start => self.row1 &= !(1 << 3),
         }
-        self.update_buttons();
+        self.resolve_directions();
     }
 
     pub fn button_up(&mut self, button: JoypadButton) {
         match button {
-            JoypadButton::Right => self.row0 |= 1 << 0,
-            JoypadButton::Left => self.row0 |= 1 << 1,
-            JoypadButton::Up => self.row0 |= 1 << 2,
-            JoypadButton::Down => self.row0 |= 1 << 3,
+            JoypadButton::Right => self.directions |= 1 << 0,
+            JoypadButton::Left => self.directions |= 1 << 1,
+            JoypadButton::Up => self.directions |= 1 << 2,
+            JoypadButton::Down => self.directions |= 1 << 3,
             JoypadButton::A => self.row1 |= 1 << 0,
             JoypadButton::B => self.row1 |= 1 << 1,
             JoypadButton::Select => self.row1 |= 1 << 2,
             JoypadButton::Start => self.row1 |= 1 << 3,
         }
-        self.update_buttons();
+        self.resolve_directions();
     }
 }
@@ -0,0 +1,100 @@
+pub const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+const MLT_REQ: u8 = 0x11;
+
+// Receives SGB command packets that are bit-banged through P14 and P15
+pub struct PacketReceiver {
+    receiving: bool,
+    bit: usize,
+    packet: [u8; PACKET_SIZE],
+    command: Vec<u8>,
+    previous_select: u8,
+    players: u8,
+    current_player: u8,
+}
+
+impl PacketReceiver {
+    pub fn new() -> Self {
+        PacketReceiver {
+            receiving: false,
+            bit: 0,
+            packet: [0; PACKET_SIZE],
+            command: Vec::new(),
+            previous_select: 0x30,
+            players: 1,
+            current_player: 0,
+        }
+    }
+
+    // Feeds a P1 write, returns a complete command once all of its packets arrived
+    pub fn write(&mut self, select: u8) -> Option<Vec<u8>> {
+        let previous_select = self.previous_select;
+        self.previous_select = select;
+
+        // MLT_REQ reads cycle through the controllers on every rising edge of P15
+        if self.players > 1 && previous_select & 0x20 == 0 && select & 0x20 != 0 {
+            self.current_player = (self.current_player + 1) % self.players;
+        }
+
+        match select {
+            // Both lines low start a new packet
+            0x00 => {
+                self.receiving = true;
+                self.bit = 0;
+                self.packet = [0; PACKET_SIZE];
+                None
+            }
+            // P14 low sends a 0, P15 low sends a 1, both high in between
+            0x10 | 0x20 if self.receiving && previous_select == 0x30 => self.receive_bit(select == 0x10),
+            _ => None,
+        }
+    }
+
+    fn receive_bit(&mut self, bit: bool) -> Option<Vec<u8>> {
+        if self.bit == PACKET_BITS {
+            // The stop bit has to be a 0, anything else drops the packet
+            self.receiving = false;
+            return match bit {
+                false => self.receive_packet(),
+                true => {
+                    self.command.clear();
+                    None
+                }
+            };
+        }
+
+        if bit {
+            self.packet[self.bit / 8] |= 1 << (self.bit % 8);
+        }
+        self.bit += 1;
+        None
+    }
+
+    fn receive_packet(&mut self) -> Option<Vec<u8>> {
+        self.command.extend_from_slice(&self.packet);
+        let length = (self.command[0] & 0x07).max(1) as usize;
+        if self.command.len() < length * PACKET_SIZE {
+            return None;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        if command[0] >> 3 == MLT_REQ {
+            self.players = match command[1] & 0x03 {
+                1 => 2,
+                3 => 4,
+                _ => 1,
+            };
+            self.current_player = 0;
+            return None;
+        }
+        Some(command)
+    }
+
+    pub fn multiplayer(&self) -> bool {
+        self.players > 1
+    }
+
+    pub fn current_player(&self) -> u8 {
+        self.current_player
+    }
+}
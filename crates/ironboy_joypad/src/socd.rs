@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+// What the game sees when opposing directions are held at once, which the d-pad of a real Game Boy can't do
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub enum SocdPolicy {
+    // Both directions reach the game
+    Allow,
+    // The direction pressed last wins, releasing it brings back the other one
+    #[default]
+    LastWins,
+    // Opposing directions cancel out
+    Neutral,
+}
+
+impl SocdPolicy {
+    pub const ALL: [SocdPolicy; 3] = [SocdPolicy::Allow, SocdPolicy::LastWins, SocdPolicy::Neutral];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SocdPolicy::Allow => "allow",
+            SocdPolicy::LastWins => "last-wins",
+            SocdPolicy::Neutral => "neutral",
+        }
+    }
+
+    // Resolves one axis from the held negative and positive directions, `positive_last` when the positive one was pressed after the other
+    pub(crate) fn resolve(&self, negative: bool, positive: bool, positive_last: bool) -> (bool, bool) {
+        match (self, negative && positive) {
+            (_, false) | (SocdPolicy::Allow, true) => (negative, positive),
+            (SocdPolicy::LastWins, true) => (!positive_last, positive_last),
+            (SocdPolicy::Neutral, true) => (false, false),
+        }
+    }
+}
+
+impl FromStr for SocdPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        SocdPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name() == s)
+            .ok_or_else(|| format!("unknown SOCD policy {s}"))
+    }
+}
@@ -0,0 +1,126 @@
+use ironboy_common::SystemMemoryAccess;
+
+use crate::{JoyPad, JoypadButton, SocdPolicy};
+
+// Bit-bangs SGB packets through P1, least significant bit first and a 0 stop bit after each
+fn send_packets(joy_pad: &mut JoyPad, data: &[u8]) {
+    for packet in data.chunks(16) {
+        joy_pad.write_8(0xFF00, 0x00);
+        joy_pad.write_8(0xFF00, 0x30);
+        let bits = packet.iter().flat_map(|byte| (0..8).map(move |bit| byte >> bit & 0x01 != 0));
+        for bit in bits.chain([false]) {
+            joy_pad.write_8(0xFF00, if bit { 0x10 } else { 0x20 });
+            joy_pad.write_8(0xFF00, 0x30);
+        }
+    }
+}
+
+fn sgb_joy_pad() -> JoyPad {
+    let mut joy_pad = JoyPad::new();
+    joy_pad.enable_sgb();
+    joy_pad
+}
+
+#[test]
+fn sgb_packets_are_assembled_into_commands() {
+    let mut joy_pad = sgb_joy_pad();
+    let mut pal01 = [0; 16];
+    pal01[..5].copy_from_slice(&[0x01, 0xFF, 0x7F, 0x1F, 0x00]);
+    send_packets(&mut joy_pad, &pal01);
+    assert_eq!(joy_pad.take_sgb_command(), Some(pal01.to_vec()));
+    assert_eq!(joy_pad.take_sgb_command(), None);
+
+    // ATTR_BLK spanning two packets is only handed out once both arrived
+    let attr_blk: Vec<u8> = [0x22, 0x03].into_iter().chain((2..32).map(|byte| byte as u8)).collect();
+    send_packets(&mut joy_pad, &attr_blk[..16]);
+    assert_eq!(joy_pad.take_sgb_command(), None);
+    send_packets(&mut joy_pad, &attr_blk[16..]);
+    assert_eq!(joy_pad.take_sgb_command(), Some(attr_blk));
+}
+
+#[test]
+fn sgb_packet_with_a_bad_stop_bit_is_dropped() {
+    let mut joy_pad = sgb_joy_pad();
+    joy_pad.write_8(0xFF00, 0x00);
+    joy_pad.write_8(0xFF00, 0x30);
+    for _ in 0..129 {
+        joy_pad.write_8(0xFF00, 0x10);
+        joy_pad.write_8(0xFF00, 0x30);
+    }
+    assert_eq!(joy_pad.take_sgb_command(), None);
+}
+
+#[test]
+fn mlt_req_cycles_through_the_players() {
+    let mut joy_pad = sgb_joy_pad();
+    let mut mlt_req = [0; 16];
+    mlt_req[..2].copy_from_slice(&[0x89, 0x03]);
+    send_packets(&mut joy_pad, &mlt_req);
+    // MLT_REQ is handled by the joypad itself
+    assert_eq!(joy_pad.take_sgb_command(), None);
+
+    // Every rising edge of P15 selects the next of the four controllers, read back as 0xF minus its index
+    joy_pad.interrupt = 0;
+    let mut ids = Vec::new();
+    for _ in 0..5 {
+        ids.push(joy_pad.read_8(0xFF00) & 0x0F);
+        joy_pad.write_8(0xFF00, 0x10);
+        joy_pad.write_8(0xFF00, 0x30);
+    }
+    assert_eq!(ids, [0xF, 0xE, 0xD, 0xC, 0xF]);
+    // The IDs aren't button presses
+    assert_eq!(joy_pad.interrupt, 0);
+
+    mlt_req[1] = 0x00;
+    send_packets(&mut joy_pad, &mlt_req);
+    joy_pad.write_8(0xFF00, 0x10);
+    joy_pad.write_8(0xFF00, 0x30);
+    assert_eq!(joy_pad.read_8(0xFF00) & 0x0F, 0xF);
+}
+
+fn directions(joy_pad: &mut JoyPad) -> u8 {
+    joy_pad.write_8(0xFF00, 0x20);
+    !joy_pad.read_8(0xFF00) & 0x0F
+}
+
+#[test]
+fn socd_policy_resolves_opposing_directions() {
+    let mut joy_pad = JoyPad::new();
+    joy_pad.button_down(JoypadButton::Left);
+    joy_pad.button_down(JoypadButton::Right);
+    assert_eq!(directions(&mut joy_pad), 0b0001);
+    joy_pad.button_up(JoypadButton::Right);
+    assert_eq!(directions(&mut joy_pad), 0b0010);
+
+    joy_pad.button_down(JoypadButton::Right);
+    joy_pad.set_socd_policy(SocdPolicy::Neutral);
+    assert_eq!(directions(&mut joy_pad), 0b0000);
+    joy_pad.set_socd_policy(SocdPolicy::Allow);
+    assert_eq!(directions(&mut joy_pad), 0b0011);
+}
+
+#[test]
+fn joypad_interrupt_follows_falling_lines() {
+    let mut joy_pad = JoyPad::new();
+    joy_pad.write_8(0xFF00, 0x10);
+    joy_pad.button_down(JoypadButton::A);
+    assert_eq!(joy_pad.interrupt, 0x10);
+
+    // A second line falling while another one is already low
+    joy_pad.interrupt = 0;
+    joy_pad.button_down(JoypadButton::B);
+    assert_eq!(joy_pad.interrupt, 0x10);
+
+    // Selecting the d-pad row drops the held buttons' lines
+    joy_pad.interrupt = 0;
+    joy_pad.write_8(0xFF00, 0x30);
+    joy_pad.button_down(JoypadButton::Down);
+    assert_eq!(joy_pad.interrupt, 0);
+    joy_pad.write_8(0xFF00, 0x20);
+    assert_eq!(joy_pad.interrupt, 0x10);
+
+    // Releasing buttons raises lines and requests nothing
+    joy_pad.interrupt = 0;
+    joy_pad.button_up(JoypadButton::Down);
+    assert_eq!(joy_pad.interrupt, 0);
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use crate::framebuffer::xrgb;
+
+const COLORS: usize = 0x8000;
+
+// RGB555 to RGB888 conversion used for Game Boy Color output
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ColorCorrection {
+    // Linear scaling of each channel, the colors exactly as the game wrote them
+    Raw,
+    // Cheap channel mixing approximating the washed out GBC LCD
+    GbcLcd,
+    // Channel mixing in linear light with the GBC LCD response and sRGB gamma
+    Accurate,
+    // Darker and less saturated screen of the Game Boy Advance
+    Gba,
+}
+
+impl ColorCorrection {
+    pub const ALL: [ColorCorrection; 4] = [
+        ColorCorrection::Raw,
+        ColorCorrection::GbcLcd,
+        ColorCorrection::Accurate,
+        ColorCorrection::Gba,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorCorrection::Raw => "raw",
+            ColorCorrection::GbcLcd => "gbc",
+            ColorCorrection::Accurate => "accurate",
+            ColorCorrection::Gba => "gba",
+        }
+    }
+
+    pub fn next(&self) -> ColorCorrection {
+        let index = ColorCorrection::ALL.iter().position(|correction| correction == self).unwrap_or_default();
+        ColorCorrection::ALL[(index + 1) % ColorCorrection::ALL.len()]
+    }
+
+    // r, g and b are 5-bit channels
+    pub fn convert(&self, r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+        match self {
+            ColorCorrection::Raw => (r << 3 | r >> 2, g << 3 | g >> 2, b << 3 | b >> 2),
+            ColorCorrection::GbcLcd => {
+                let (r, g, b) = (r as u32, g as u32, b as u32);
+                let red = ((r * 13 + g * 2 + b) >> 1) as u8;
+                let green = ((g * 3 + b) << 1) as u8;
+                let blue = ((r * 3 + g * 2 + b * 11) >> 1) as u8;
+                (red, green, blue)
+            }
+            ColorCorrection::Accurate => {
+                let matrix = [[0.82, 0.24, -0.06], [0.125, 0.665, 0.21], [0.195, 0.075, 0.73]];
+                mix(r, g, b, 2.2, matrix, 0.94)
+            }
+            ColorCorrection::Gba => {
+                let matrix = [[0.845, 0.17, -0.015], [0.09, 0.68, 0.23], [0.16, 0.085, 0.755]];
+                mix(r, g, b, 2.7, matrix, 0.93)
+            }
+        }
+    }
+
+    // XRGB8888 for every RGB555 value, indexed by r | g << 5 | b << 10
+    pub fn table(&self) -> Vec<u32> {
+        (0..COLORS)
+            .map(|color| {
+                let (r, g, b) = self.convert(color as u8 & 0x1F, (color >> 5) as u8 & 0x1F, (color >> 10) as u8 & 0x1F);
+                xrgb(r, g, b)
+            })
+            .collect()
+    }
+}
+
+fn mix(r: u8, g: u8, b: u8, lcd_gamma: f32, matrix: [[f32; 3]; 3], luminance: f32) -> (u8, u8, u8) {
+    let linear = [r, g, b].map(|channel| (channel as f32 / 31.0).powf(lcd_gamma));
+    let [red, green, blue] = matrix.map(|row| {
+        let value = row.iter().zip(linear).map(|(weight, channel)| weight * channel).sum::<f32>() * luminance;
+        (value.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8
+    });
+    (red, green, blue)
+}
+
+impl FromStr for ColorCorrection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        ColorCorrection::ALL
+            .into_iter()
+            .find(|correction| correction.name() == s)
+            .ok_or_else(|| format!("unknown color correction {s}"))
+    }
+}
@@ -0,0 +1,238 @@
+use std::str::FromStr;
+
+// Colors the CGB boot ROM loads for a DMG game: BGP maps onto BG palette 0, OBP0 and OBP1 onto OBJ palettes 0 and 1.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CompatibilityPalette {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl CompatibilityPalette {
+    pub(crate) const fn new(bg: [u32; 4], obj0: [u32; 4], obj1: [u32; 4]) -> Self {
+        CompatibilityPalette { bg, obj0, obj1 }
+    }
+
+    pub(crate) const fn uniform(colors: [u32; 4]) -> Self {
+        CompatibilityPalette::new(colors, colors, colors)
+    }
+
+    // Palette picked by the boot ROM, only games licensed by Nintendo get a dedicated one
+    pub fn for_title(title: &[u8], licensed_by_nintendo: bool) -> Self {
+        if !licensed_by_nintendo {
+            return DEFAULT_PALETTE;
+        }
+
+        let checksum = title.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        let fourth_letter = title.get(3).copied().unwrap_or(0);
+        let combination = match TITLE_CHECKSUMS.iter().position(|&sum| sum == checksum) {
+            Some(index) if index < SHARED_CHECKSUMS => TITLE_COMBINATIONS[index],
+            // The fourth letters of every game sharing a checksum follow each other with a stride of the shared checksum count
+            Some(index) => (index - SHARED_CHECKSUMS..FOURTH_LETTERS.len())
+                .step_by(TITLE_CHECKSUMS.len() - SHARED_CHECKSUMS)
+                .find(|&letter| FOURTH_LETTERS[letter] == fourth_letter)
+                .map_or(TITLE_COMBINATIONS[0], |letter| TITLE_COMBINATIONS[SHARED_CHECKSUMS + letter]),
+            None => TITLE_COMBINATIONS[0],
+        };
+        let [obj0, obj1, bg] = COMBINATIONS[combination as usize];
+        CompatibilityPalette::new(boot_palette(bg), boot_palette(obj0), boot_palette(obj1))
+    }
+}
+
+const RED: [u32; 4] = [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000];
+const GREEN: [u32; 4] = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+const BLUE: [u32; 4] = [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000];
+const BROWN: [u32; 4] = [0xFFFFFF, 0xFFAD63, 0x843100, 0x000000];
+
+// Right + A, also used for every game without an entry in the title table
+pub const DEFAULT_PALETTE: CompatibilityPalette = CompatibilityPalette::new([0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000], RED, RED);
+
+// RGB555 colors stored in the boot ROM, four per palette
+const BOOT_COLORS: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, 0x639F, 0x4279, 0x15B0, 0x04CB, 0x7FFF, 0x6E31, 0x454A, 0x0000, 0x7FFF, 0x1BEF, 0x0200, 0x0000, 0x7FFF, 0x421F,
+    0x1CF2, 0x0000, 0x7FFF, 0x5294, 0x294A, 0x0000, 0x7FFF, 0x03FF, 0x012F, 0x0000, 0x7FFF, 0x03EF, 0x01D6, 0x0000, 0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000, 0x67FF, 0x77AC, 0x1A13, 0x2D6B, 0x7ED6, 0x4BFF, 0x2175, 0x0000, 0x53FF, 0x4A5F, 0x7E52, 0x0000, 0x4FFF, 0x7ED2,
+    0x3A4C, 0x1CE0, 0x03ED, 0x7FFF, 0x255F, 0x0000, 0x036A, 0x021F, 0x03FF, 0x7FFF, 0x7FFF, 0x01DF, 0x0112, 0x0000, 0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000, 0x299F, 0x001A, 0x000C, 0x0000, 0x7FFF, 0x027F, 0x001F, 0x0000, 0x7FFF, 0x03E0, 0x0206, 0x0120, 0x7FFF, 0x7EEB,
+    0x001F, 0x7C00, 0x7FFF, 0x3FFF, 0x7E00, 0x001F, 0x7FFF, 0x03FF, 0x001F, 0x0000, 0x03FF, 0x001F, 0x000C, 0x0000, 0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF, 0x7FFF, 0x7E8C, 0x7C00, 0x0000, 0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+const fn palettes(obj0: usize, obj1: usize, bg: usize) -> [usize; 3] {
+    [obj0 * 4, obj1 * 4, bg * 4]
+}
+
+// Offsets in `BOOT_COLORS` of the OBJ0, OBJ1 and BG colors, a few of them start in the middle of a palette
+const COMBINATIONS: [[usize; 3]; 51] = [
+    palettes(4, 4, 29),
+    palettes(18, 18, 18),
+    palettes(20, 20, 20),
+    palettes(24, 24, 24),
+    palettes(9, 9, 9),
+    palettes(0, 0, 0),
+    palettes(27, 27, 27),
+    palettes(5, 5, 5),
+    palettes(12, 12, 12),
+    palettes(26, 26, 26),
+    palettes(16, 8, 8),
+    palettes(4, 28, 28),
+    palettes(4, 2, 2),
+    palettes(3, 4, 4),
+    palettes(4, 29, 29),
+    palettes(28, 4, 28),
+    palettes(2, 17, 2),
+    palettes(16, 16, 8),
+    palettes(4, 4, 7),
+    palettes(4, 4, 18),
+    palettes(4, 4, 20),
+    palettes(19, 19, 9),
+    [15, 15, 44],
+    palettes(17, 17, 2),
+    palettes(4, 4, 2),
+    palettes(4, 4, 3),
+    palettes(28, 28, 0),
+    palettes(3, 3, 0),
+    palettes(0, 0, 1),
+    palettes(18, 22, 18),
+    palettes(20, 22, 20),
+    palettes(24, 22, 24),
+    palettes(16, 22, 8),
+    palettes(17, 4, 13),
+    [111, 0, 56],
+    [111, 16, 60],
+    palettes(19, 22, 9),
+    palettes(16, 28, 10),
+    palettes(4, 23, 28),
+    palettes(17, 22, 2),
+    palettes(4, 0, 2),
+    palettes(4, 28, 3),
+    palettes(28, 3, 0),
+    palettes(3, 28, 4),
+    palettes(21, 28, 4),
+    palettes(3, 28, 0),
+    palettes(25, 3, 28),
+    palettes(0, 28, 8),
+    palettes(4, 3, 28),
+    palettes(28, 3, 6),
+    palettes(4, 28, 29),
+];
+
+// Sum of the title bytes, the checksums from `SHARED_CHECKSUMS` on belong to several games
+const TITLE_CHECKSUMS: [u8; 79] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70, 0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA,
+    0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97, 0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F, 0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27,
+    0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+];
+const SHARED_CHECKSUMS: usize = 65;
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// Index in `COMBINATIONS` for every unique checksum, then for every fourth letter
+const TITLE_COMBINATIONS: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29, 5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42,
+    45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6, 5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 32, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24,
+    31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+fn boot_palette(offset: usize) -> [u32; 4] {
+    let channel = |color: u16, shift: u16| ((color >> shift & 0x1F) as u32 * 255 + 15) / 31;
+    let colors = &BOOT_COLORS[offset..offset + 4];
+    [0, 1, 2, 3].map(|i| channel(colors[i], 0) << 16 | channel(colors[i], 5) << 8 | channel(colors[i], 10))
+}
+
+// Direction and button held while the boot logo is shown
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PaletteCombo {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+impl PaletteCombo {
+    pub const ALL: [PaletteCombo; 12] = [
+        PaletteCombo::Up,
+        PaletteCombo::UpA,
+        PaletteCombo::UpB,
+        PaletteCombo::Left,
+        PaletteCombo::LeftA,
+        PaletteCombo::LeftB,
+        PaletteCombo::Down,
+        PaletteCombo::DownA,
+        PaletteCombo::DownB,
+        PaletteCombo::Right,
+        PaletteCombo::RightA,
+        PaletteCombo::RightB,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteCombo::Up => "up",
+            PaletteCombo::UpA => "up+a",
+            PaletteCombo::UpB => "up+b",
+            PaletteCombo::Left => "left",
+            PaletteCombo::LeftA => "left+a",
+            PaletteCombo::LeftB => "left+b",
+            PaletteCombo::Down => "down",
+            PaletteCombo::DownA => "down+a",
+            PaletteCombo::DownB => "down+b",
+            PaletteCombo::Right => "right",
+            PaletteCombo::RightA => "right+a",
+            PaletteCombo::RightB => "right+b",
+        }
+    }
+
+    pub fn color_name(&self) -> &'static str {
+        match self {
+            PaletteCombo::Up => "brown",
+            PaletteCombo::UpA => "red",
+            PaletteCombo::UpB => "dark-brown",
+            PaletteCombo::Left => "blue",
+            PaletteCombo::LeftA => "dark-blue",
+            PaletteCombo::LeftB => "grayscale",
+            PaletteCombo::Down => "pastel",
+            PaletteCombo::DownA => "orange",
+            PaletteCombo::DownB => "yellow",
+            PaletteCombo::Right => "green",
+            PaletteCombo::RightA => "dark-green",
+            PaletteCombo::RightB => "inverted",
+        }
+    }
+
+    pub fn palette(&self) -> CompatibilityPalette {
+        match self {
+            PaletteCombo::Up => CompatibilityPalette::uniform(BROWN),
+            PaletteCombo::UpA => CompatibilityPalette::new(RED, GREEN, BLUE),
+            PaletteCombo::UpB => CompatibilityPalette::new([0xFFE6C5, 0xCE9C84, 0x846B29, 0x5A3108], BROWN, BROWN),
+            PaletteCombo::Left => CompatibilityPalette::new(BLUE, RED, GREEN),
+            PaletteCombo::LeftA => CompatibilityPalette::new([0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000], RED, BROWN),
+            PaletteCombo::LeftB => CompatibilityPalette::uniform([0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000]),
+            PaletteCombo::Down => CompatibilityPalette::uniform([0xFFFFA5, 0xFF9494, 0x9494FF, 0x000000]),
+            PaletteCombo::DownA => CompatibilityPalette::uniform([0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000]),
+            PaletteCombo::DownB => CompatibilityPalette::new([0xFFFFFF, 0xFFFF00, 0x7B4A00, 0x000000], BLUE, GREEN),
+            PaletteCombo::Right => CompatibilityPalette::uniform([0xFFFFFF, 0x52FF00, 0xFF4200, 0x000000]),
+            PaletteCombo::RightA => DEFAULT_PALETTE,
+            PaletteCombo::RightB => CompatibilityPalette::uniform([0x000000, 0x008484, 0xFFDE00, 0xFFFFFF]),
+        }
+    }
+}
+
+impl FromStr for PaletteCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        PaletteCombo::ALL
+            .into_iter()
+            .find(|combo| combo.name() == s || combo.color_name() == s)
+            .ok_or_else(|| format!("unknown palette {s}"))
+    }
+}
@@ -0,0 +1,138 @@
+use std::{path::Path, str::FromStr};
+
+use thiserror::Error;
+
+// Colors a monochrome Game Boy shows for the four shades of each layer, from lightest to darkest
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DmgPalette {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl DmgPalette {
+    pub const fn new(bg: [u32; 4], obj0: [u32; 4], obj1: [u32; 4]) -> Self {
+        DmgPalette { bg, obj0, obj1 }
+    }
+
+    pub const fn uniform(colors: [u32; 4]) -> Self {
+        DmgPalette::new(colors, colors, colors)
+    }
+
+    // Lines of `bg`, `obj0` or `obj1` followed by four hex colors from lightest to darkest, lines starting with `#` are comments.
+    // Object layers left out use the background colors.
+    pub fn parse(text: &str) -> Result<Self, PaletteError> {
+        let mut bg = None;
+        let mut obj0 = None;
+        let mut obj1 = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (layer, colors) = line
+                .split_once(['=', ':'])
+                .or_else(|| line.split_once(char::is_whitespace))
+                .ok_or(PaletteError::InvalidLine(index + 1))?;
+            let colors = parse_colors(colors).ok_or(PaletteError::InvalidLine(index + 1))?;
+            match layer.trim().to_ascii_lowercase().as_str() {
+                "bg" => bg = Some(colors),
+                "obj0" => obj0 = Some(colors),
+                "obj1" => obj1 = Some(colors),
+                layer => return Err(PaletteError::UnknownLayer(layer.to_string())),
+            }
+        }
+
+        let bg = bg.ok_or(PaletteError::MissingBackground)?;
+        Ok(DmgPalette::new(bg, obj0.unwrap_or(bg), obj1.unwrap_or(bg)))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, PaletteError> {
+        let text = std::fs::read_to_string(path).map_err(|error| PaletteError::ReadFailure(error.to_string()))?;
+        DmgPalette::parse(&text)
+    }
+}
+
+fn parse_colors(text: &str) -> Option<[u32; 4]> {
+    let mut colors = [0; 4];
+    let mut values = text.split([' ', ',', '\t']).filter(|value| !value.is_empty());
+    for color in colors.iter_mut() {
+        let value = values.next()?;
+        let value = value.trim_start_matches('#').trim_start_matches("0x");
+        if value.len() != 6 {
+            return None;
+        }
+        *color = u32::from_str_radix(value, 16).ok()?;
+    }
+    values.next().is_none().then_some(colors)
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DmgPalettePreset {
+    Gray,
+    // Green tinted LCD of the original DMG
+    Green,
+    // Black and white LCD of the Game Boy Pocket
+    Pocket,
+    // Electroluminescent backlight of the Game Boy Light
+    Light,
+}
+
+impl DmgPalettePreset {
+    pub const ALL: [DmgPalettePreset; 4] = [
+        DmgPalettePreset::Gray,
+        DmgPalettePreset::Green,
+        DmgPalettePreset::Pocket,
+        DmgPalettePreset::Light,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DmgPalettePreset::Gray => "gray",
+            DmgPalettePreset::Green => "green",
+            DmgPalettePreset::Pocket => "pocket",
+            DmgPalettePreset::Light => "light",
+        }
+    }
+
+    pub fn palette(&self) -> DmgPalette {
+        match self {
+            DmgPalettePreset::Gray => DmgPalette::uniform([0xFFFFFF, 0xC0C0C0, 0x606060, 0x000000]),
+            DmgPalettePreset::Green => DmgPalette::uniform([0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F]),
+            DmgPalettePreset::Pocket => DmgPalette::uniform([0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F]),
+            DmgPalettePreset::Light => DmgPalette::uniform([0x00B581, 0x009A71, 0x00694A, 0x004F3B]),
+        }
+    }
+
+    pub fn next(&self) -> DmgPalettePreset {
+        let index = DmgPalettePreset::ALL.iter().position(|preset| preset == self).unwrap_or_default();
+        DmgPalettePreset::ALL[(index + 1) % DmgPalettePreset::ALL.len()]
+    }
+}
+
+impl FromStr for DmgPalettePreset {
+    type Err = PaletteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        DmgPalettePreset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s)
+            .ok_or(PaletteError::UnknownPreset(s))
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PaletteError {
+    #[error("Palette file could not be read: `{0}`")]
+    ReadFailure(String),
+    #[error("Palette line {0} needs a layer and four hex colors")]
+    InvalidLine(usize),
+    #[error("Unknown palette layer `{0}`, expected bg, obj0 or obj1")]
+    UnknownLayer(String),
+    #[error("Palette file has no bg colors")]
+    MissingBackground,
+    #[error("Unknown palette preset `{0}`")]
+    UnknownPreset(String),
+}
@@ -0,0 +1,52 @@
+use crate::framebuffer::{FrameBuffer, unpack, xrgb};
+
+// Emulates the slow response of the LCD, pixels fade towards each new frame instead of switching at once,
+// which blends sprites that games flicker every other frame into transparency
+pub struct FrameBlending {
+    // Share of the previous output that is still visible in the next one
+    persistence: f32,
+    frame: Vec<[f32; 3]>,
+    output: FrameBuffer,
+}
+
+impl FrameBlending {
+    pub fn new(persistence: f32, width: usize, height: usize) -> Self {
+        FrameBlending {
+            persistence: persistence.clamp(0.0, 1.0),
+            frame: Vec::new(),
+            output: FrameBuffer::new(width, height, false),
+        }
+    }
+
+    pub fn persistence(&self) -> f32 {
+        self.persistence
+    }
+
+    pub fn blend(&mut self, screen: &FrameBuffer) {
+        // The first frame has nothing to fade from
+        if self.frame.is_empty() || screen.indices().is_some() != self.output.indices().is_some() {
+            self.frame = screen.pixels().iter().map(|&color| channels(color)).collect();
+            self.output = screen.clone();
+            return;
+        }
+
+        let persistence = self.persistence;
+        for (offset, (blended, &color)) in self.frame.iter_mut().zip(screen.pixels()).enumerate() {
+            for (channel, value) in blended.iter_mut().zip(channels(color)) {
+                *channel = *channel * persistence + value * (1.0 - persistence);
+            }
+            let [r, g, b] = blended.map(|channel| channel.round() as u8);
+            let index = screen.indices().map_or(0, |indices| indices[offset]);
+            self.output.set(offset, xrgb(r, g, b), index);
+        }
+    }
+
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.output
+    }
+}
+
+fn channels(color: u32) -> [f32; 3] {
+    let (r, g, b) = unpack(color);
+    [r as f32, g as f32, b as f32]
+}
@@ -0,0 +1,78 @@
+// Packs a color as XRGB8888, the unused top byte is left at zero
+pub const fn xrgb(r: u8, g: u8, b: u8) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+pub const fn unpack(color: u32) -> (u8, u8, u8) {
+    ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+// Fixed size picture in XRGB8888, optionally with the 2-bit tile color index each pixel was drawn from
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+    indices: Option<Vec<u8>>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, indices: bool) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            indices: indices.then(|| vec![0; width * height]),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    pub fn indices(&self) -> Option<&[u8]> {
+        self.indices.as_deref()
+    }
+
+    // Pixels as bytes in memory order, B G R X on little endian hosts
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.pixels)
+    }
+
+    // Tightly packed R G B bytes, e.g. for image encoders
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&color| <[u8; 3]>::from(unpack(color))).collect()
+    }
+
+    pub fn set(&mut self, offset: usize, color: u32, index: u8) {
+        self.pixels[offset] = color;
+        if let Some(indices) = self.indices.as_mut() {
+            indices[offset] = index;
+        }
+    }
+
+    pub fn fill(&mut self, color: u32, index: u8) {
+        self.pixels.fill(color);
+        if let Some(indices) = self.indices.as_mut() {
+            indices.fill(index);
+        }
+    }
+
+    pub fn set_index_buffer(&mut self, enabled: bool) {
+        if enabled != self.indices.is_some() {
+            self.indices = enabled.then(|| vec![0; self.width * self.height]);
+        }
+    }
+}
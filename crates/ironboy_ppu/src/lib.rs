@@ -1,17 +1,27 @@
 use background::Background;
 use bg_attributes::BgMapAttributes;
-use ironboy_common::{CPU_CLOCK_SPEED, GameBoyMode, SystemMemoryAccess};
+use frame_blending::FrameBlending;
+use ironboy_common::{CPU_CLOCK_SPEED, GameBoyMode, OamAccess, SystemMemoryAccess};
 use oam::Oam;
 use palette::{CgbPalette, Palette, color_index};
 use registers::{PpuMode, lcd_control::LcdControl, lcd_status::LcdStatus};
+use sgb::{Sgb, TRANSFER_SIZE};
 use tile::{TILE_HEIGHT, TILE_WIDTH};
 use window::Window;
 
 mod background;
 mod bg_attributes;
+mod color_correction;
+mod compatibility;
+mod dmg_palette;
+mod frame_blending;
+mod framebuffer;
 mod oam;
 mod palette;
 mod registers;
+mod sgb;
+#[cfg(test)]
+mod tests;
 mod tile;
 mod window;
 
@@ -20,6 +30,11 @@ const OAM_SIZE: usize = 40;
 pub const VIEWPORT_WIDTH: usize = 160;
 pub const VIEWPORT_HEIGHT: usize = 144;
 pub const FULL_WIDTH: usize = 256;
+pub use color_correction::ColorCorrection;
+pub use compatibility::{CompatibilityPalette, PaletteCombo};
+pub use dmg_palette::{DmgPalette, DmgPalettePreset, PaletteError};
+pub use framebuffer::{FrameBuffer, unpack, xrgb};
+pub use sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH};
 
 const OAM_CYCLES: u32 = 80;
 const DRAWING_PIXELS_CYCLES: u32 = 172;
@@ -41,17 +56,24 @@ pub struct Ppu {
     obj1_palette: Palette,
     cgb_bg_palette: CgbPalette,
     cgb_obj_palette: CgbPalette,
+    dmg_palette: DmgPalette,
+    color_correction: ColorCorrection,
+    color_table: Vec<u32>,
+    frame_blending: Option<FrameBlending>,
     pub vram: [u8; VRAM_SIZE],
     oam: [Oam; OAM_SIZE],
     oam_buffer: Vec<(usize, u8)>,
     object_height: u8,
     line_priority: [(u8, bool); VIEWPORT_WIDTH],
-    pub screen_buffer: Vec<(u8, u8, u8)>,
+    // Drawn into while the next frame is rendered, swapped with the front buffer at VBlank
+    back_buffer: FrameBuffer,
+    front_buffer: FrameBuffer,
     pub screen_updated: bool,
     pub interrupt: u8,
     vram_bank: usize,
     is_hblanking: bool,
     game_boy_mode: GameBoyMode,
+    sgb: Option<Sgb>,
 }
 
 impl SystemMemoryAccess for Ppu {
@@ -106,14 +128,14 @@ impl SystemMemoryAccess for Ppu {
             0xFF69 => self.cgb_bg_palette.write_palette(value),
             0xFF6A => self.cgb_obj_palette.write_spec_and_index(value),
             0xFF6B => self.cgb_obj_palette.write_palette(value),
-            _ => panic!("PPU does not handle write {:04X}", address),
+            _ => {}
         }
     }
 }
 
 impl Ppu {
     pub fn new(mode: GameBoyMode) -> Ppu {
-        Ppu {
+        let mut ppu = Ppu {
             line_cycles: 32,
             ly: 0,
             lyc: 0,
@@ -126,18 +148,26 @@ impl Ppu {
             obj1_palette: Palette::new(1),
             cgb_bg_palette: CgbPalette::new(),
             cgb_obj_palette: CgbPalette::new(),
+            dmg_palette: DmgPalettePreset::Gray.palette(),
+            color_correction: ColorCorrection::GbcLcd,
+            color_table: ColorCorrection::GbcLcd.table(),
+            frame_blending: None,
             vram: [0; VRAM_SIZE],
             oam: [Oam::new(); OAM_SIZE],
             oam_buffer: Vec::new(),
             object_height: TILE_HEIGHT,
             line_priority: [(0, false); VIEWPORT_WIDTH],
-            screen_buffer: vec![(0, 0, 0); VIEWPORT_WIDTH * VIEWPORT_HEIGHT],
+            back_buffer: FrameBuffer::new(VIEWPORT_WIDTH, VIEWPORT_HEIGHT, false),
+            front_buffer: FrameBuffer::new(VIEWPORT_WIDTH, VIEWPORT_HEIGHT, false),
             screen_updated: false,
             interrupt: 0,
             vram_bank: 0,
             is_hblanking: false,
             game_boy_mode: mode,
-        }
+            sgb: (mode == GameBoyMode::SuperGameBoy).then(Sgb::new),
+        };
+        ppu.set_compatibility_palette(&compatibility::DEFAULT_PALETTE);
+        ppu
     }
 
     pub fn cycle(&mut self, cycles: u32) {
@@ -161,7 +191,7 @@ impl Ppu {
                 true => {
                     if self.lcd_status.mode() != PpuMode::VBlank {
                         self.interrupt |= 0x01;
-                        self.screen_updated = true;
+                        self.finish_frame();
                         self.window.reset_line_counter();
                         if self.lcd_status.set_mode(PpuMode::VBlank) {
                             self.interrupt |= 0x02;
@@ -196,10 +226,116 @@ impl Ppu {
         }
     }
 
+    // The latest completed frame, with the SGB border composited in SGB mode
+    pub fn frame(&self) -> &FrameBuffer {
+        match &self.sgb {
+            Some(sgb) => sgb.frame(),
+            None => match &self.frame_blending {
+                Some(blending) => blending.frame(),
+                None => &self.front_buffer,
+            },
+        }
+    }
+
+    // Also records the 2-bit color index of every pixel as read from its tile, before BGP, OBP0/OBP1 or the CGB palettes
+    // are applied, e.g. for custom palettes applied by a frontend. Pixels of a disabled background or screen are 0
+    pub fn set_index_buffer(&mut self, enabled: bool) {
+        self.back_buffer.set_index_buffer(enabled);
+        self.front_buffer.set_index_buffer(enabled);
+    }
+
+    // Blends each frame into the previous ones, None shows every frame as it was drawn
+    pub fn set_frame_blending(&mut self, persistence: Option<f32>) {
+        self.frame_blending = persistence.map(|persistence| FrameBlending::new(persistence, VIEWPORT_WIDTH, VIEWPORT_HEIGHT));
+    }
+
+    pub fn frame_blending(&self) -> Option<f32> {
+        self.frame_blending.as_ref().map(FrameBlending::persistence)
+    }
+
+    pub fn frame_size(&self) -> (usize, usize) {
+        match self.sgb {
+            Some(_) => (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT),
+            None => (VIEWPORT_WIDTH, VIEWPORT_HEIGHT),
+        }
+    }
+
+    pub fn sgb_command(&mut self, command: &[u8]) {
+        let (vram, lcd_control, background) = (&self.vram, &self.lcd_control, &self.background);
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.command(command, || sgb_transfer_data(vram, lcd_control, background));
+        }
+    }
+
+    // Loads the colors a DMG game is shown with on CGB hardware, ignored in every other mode
+    pub fn set_compatibility_palette(&mut self, palette: &CompatibilityPalette) {
+        if self.game_boy_mode != GameBoyMode::ColorAsMonochrome {
+            return;
+        }
+
+        for color in 0..4 {
+            self.cgb_bg_palette.set_color(0, color, palette.bg[color]);
+            self.cgb_obj_palette.set_color(0, color, palette.obj0[color]);
+            self.cgb_obj_palette.set_color(1, color, palette.obj1[color]);
+        }
+    }
+
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.dmg_palette = palette;
+    }
+
+    pub fn dmg_palette(&self) -> &DmgPalette {
+        &self.dmg_palette
+    }
+
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        if correction != self.color_correction {
+            self.color_correction = correction;
+            self.color_table = correction.table();
+        }
+    }
+
+    pub fn color_correction(&self) -> ColorCorrection {
+        self.color_correction
+    }
+
     pub fn is_hblanking(&self) -> bool {
         self.is_hblanking
     }
 
+    // OAM bug: the row the PPU is scanning gets mixed with the previous one
+    pub fn corrupt_oam(&mut self, access: OamAccess) {
+        if !self.lcd_control.lcd_enabled() || self.lcd_status.mode() != PpuMode::OamScan {
+            return;
+        }
+        // One 8-byte row, two objects, is scanned every M-cycle and the first row is never affected
+        let row = (self.line_cycles / 4) as u16;
+        if row == 0 || row as usize >= OAM_SIZE / 2 {
+            return;
+        }
+
+        let word = |ppu: &Ppu, row: u16, index: u16| {
+            let address = row * 8 + index * 2;
+            u16::from_le_bytes([ppu.read_oam(address), ppu.read_oam(address + 1)])
+        };
+        let (a, b, c) = (word(self, row, 0), word(self, row - 1, 0), word(self, row - 1, 2));
+        let first = match access {
+            OamAccess::Read => b | (a & c),
+            OamAccess::Write => ((a ^ c) & (b ^ c)) ^ c,
+        };
+        let mut words = [first, 0, 0, 0];
+        for (index, value) in words.iter_mut().enumerate().skip(1) {
+            *value = word(self, row - 1, index as u16);
+        }
+
+        for (index, value) in words.iter().enumerate() {
+            let address = row * 8 + index as u16 * 2;
+            let [low, high] = value.to_le_bytes();
+            self.write_oam(address, low);
+            self.write_oam(address + 1, high);
+        }
+    }
+
     fn read_oam(&self, address: u16) -> u8 {
         let index = (address / 4) as usize;
         let oam_address = (address % 4) as usize;
@@ -207,8 +343,7 @@ impl Ppu {
             0 => self.oam[index].y_position(),
             1 => self.oam[index].x_position(),
             2 => self.oam[index].tile_index(),
-            3 => self.oam[index].attributes().into(),
-            _ => unreachable!(),
+            _ => self.oam[index].attributes().into(),
         }
     }
 
@@ -220,8 +355,7 @@ impl Ppu {
             0 => self.oam[index].set_y_position(value),
             1 => self.oam[index].set_x_position(value),
             2 => self.oam[index].set_tile_index(value),
-            3 => self.oam[index].set_attributes(value),
-            _ => unreachable!(),
+            _ => self.oam[index].set_attributes(value),
         }
     }
 
@@ -263,14 +397,30 @@ impl Ppu {
     }
 
     fn clear_screen(&mut self) {
-        self.screen_buffer.fill((255, 255, 255));
+        self.back_buffer.fill(xrgb(255, 255, 255), 0);
         self.line_priority.fill((0, false));
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.clear();
+        }
+        self.finish_frame();
+    }
+
+    fn finish_frame(&mut self) {
         self.screen_updated = true;
+        std::mem::swap(&mut self.back_buffer, &mut self.front_buffer);
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.render_frame();
+        }
+        if let Some(blending) = self.frame_blending.as_mut() {
+            blending.blend(&self.front_buffer);
+        }
     }
 
     fn render_scanline(&mut self) {
         if self.lcd_control.bg_window_enabled() || self.game_boy_mode == GameBoyMode::Color {
             self.render_bg_window_line();
+        } else {
+            self.clear_bg_window_line();
         }
 
         if self.lcd_control.object_enabled() {
@@ -303,13 +453,37 @@ impl Ppu {
             let color_index = color_index(byte1, byte2, x_offset);
             self.line_priority[lx as usize] = (color_index, bg_map_attributes.priority());
 
-            let color = if self.game_boy_mode == GameBoyMode::Color {
-                self.cgb_bg_palette.pixel_color(bg_map_attributes.color_palette(), color_index)
-            } else {
-                self.bg_palette.pixel_color(color_index)
+            let shade = self.bg_palette.shade(color_index);
+            let color = match self.game_boy_mode {
+                GameBoyMode::Color => {
+                    let palette = bg_map_attributes.color_palette();
+                    self.cgb_bg_palette.pixel_color(palette, color_index, &self.color_table)
+                }
+                GameBoyMode::ColorAsMonochrome => self.cgb_bg_palette.pixel_color(0, shade, &self.color_table),
+                _ => self.bg_palette.pixel_color(color_index, &self.dmg_palette.bg),
             };
             let offset = lx as usize + self.ly as usize * VIEWPORT_WIDTH;
-            self.screen_buffer[offset] = color
+            if let Some(sgb) = self.sgb.as_mut() {
+                sgb.set_shade(offset, shade);
+            }
+            self.back_buffer.set(offset, color, color_index);
+        }
+    }
+
+    // With LCDC.0 clear a DMG shows blank white behind the objects, every pixel of the line must still be drawn
+    // since the back buffer holds the frame from two VBlanks ago
+    fn clear_bg_window_line(&mut self) {
+        let color = match self.game_boy_mode {
+            GameBoyMode::ColorAsMonochrome => self.cgb_bg_palette.pixel_color(0, 0, &self.color_table),
+            _ => self.dmg_palette.bg[0],
+        };
+        for lx in 0..VIEWPORT_WIDTH {
+            self.line_priority[lx] = (0, false);
+            let offset = lx + self.ly as usize * VIEWPORT_WIDTH;
+            if let Some(sgb) = self.sgb.as_mut() {
+                sgb.set_shade(offset, 0);
+            }
+            self.back_buffer.set(offset, color, 0);
         }
     }
 
@@ -373,20 +547,29 @@ impl Ppu {
                         continue;
                     }
 
-                    let color = self.cgb_obj_palette.pixel_color(color_palette_index, color_index);
-                    self.screen_buffer[offset] = color;
+                    let color = self.cgb_obj_palette.pixel_color(color_palette_index, color_index, &self.color_table);
+                    self.back_buffer.set(offset, color, color_index);
                 } else {
                     if oam_entry.attributes().priority() && self.line_priority[lx as usize].0 != 0 {
                         continue;
                     }
 
-                    let object_pallete = if oam_entry.attributes().dmg_palette() {
-                        self.obj1_palette
+                    let (object_pallete, colors) = if oam_entry.attributes().dmg_palette() {
+                        (self.obj1_palette, &self.dmg_palette.obj1)
+                    } else {
+                        (self.obj0_palette, &self.dmg_palette.obj0)
+                    };
+                    let shade = object_pallete.shade(color_index);
+                    let color = if self.game_boy_mode == GameBoyMode::ColorAsMonochrome {
+                        let palette = oam_entry.attributes().dmg_palette() as u8;
+                        self.cgb_obj_palette.pixel_color(palette, shade, &self.color_table)
                     } else {
-                        self.obj0_palette
+                        object_pallete.pixel_color(color_index, colors)
                     };
-                    let color = object_pallete.pixel_color(color_index);
-                    self.screen_buffer[offset] = color;
+                    self.back_buffer.set(offset, color, color_index);
+                    if let Some(sgb) = self.sgb.as_mut() {
+                        sgb.set_shade(offset, shade);
+                    }
                 }
             }
         }
@@ -429,3 +612,18 @@ impl Ppu {
         self.vram[0x2000 + address as usize - 0x8000]
     }
 }
+
+// The SGB copies transfers from the screen, 256 tiles taken from the scrolled background map in display order.
+// Games align the scroll to whole tiles while transferring, a partial tile is taken from its top-left pixel
+fn sgb_transfer_data(vram: &[u8], lcd_control: &LcdControl, background: &Background) -> Vec<u8> {
+    let mut data = Vec::with_capacity(TRANSFER_SIZE);
+    for tile in 0..256 {
+        let lx = ((tile % 20) * TILE_WIDTH as usize) as u8;
+        let ly = ((tile / 20) * TILE_HEIGHT as usize) as u8;
+        let (x, y) = background.tile_map_coordinates(lx, ly);
+        let tile_index = vram[lcd_control.bg_tile_map().tile_index_address(x, y) as usize - 0x8000];
+        let tile_address = lcd_control.tile_data().tile_address(tile_index) as usize - 0x8000;
+        data.extend_from_slice(&vram[tile_address..tile_address + 16]);
+    }
+    data
+}
@@ -52,7 +52,7 @@ impl From<&Attributes> for u8 {
             | (flags.x_flip as u8) << 5
             | (flags.dmg_palette as u8) << 4
             | (flags.bank as u8) << 3
-            | (flags.cgb_palette as u8) << 2
+            | flags.cgb_palette
     }
 }
 
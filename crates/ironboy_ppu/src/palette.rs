@@ -12,13 +12,12 @@ impl Palette {
         pallete
     }
 
-    pub fn pixel_color(&self, color: u8) -> (u8, u8, u8) {
-        match self.data[color as usize] {
-            0 => (255, 255, 255), // white
-            1 => (192, 192, 192), // light gray
-            2 => (96, 96, 96),    // dark gray
-            _ => (0, 0, 0),       // black
-        }
+    pub fn shade(&self, color: u8) -> u8 {
+        self.data[color as usize]
+    }
+
+    pub fn pixel_color(&self, color: u8, colors: &[u32; 4]) -> u32 {
+        colors[self.data[color as usize] as usize]
     }
 
     pub fn write(&mut self, value: u8) {
@@ -58,17 +57,15 @@ impl CgbPalette {
         }
     }
 
-    pub fn pixel_color(&self, palette: u8, color: u8) -> (u8, u8, u8) {
-        let r = self.data[palette as usize][color as usize][0] as u32;
-        let g = self.data[palette as usize][color as usize][1] as u32;
-        let b = self.data[palette as usize][color as usize][2] as u32;
-
-        //adjust colors more??
-        let red = ((r * 13 + g * 2 + b) >> 1) as u8;
-        let green = ((g * 3 + b) << 1) as u8;
-        let blue = ((r * 3 + g * 2 + b * 11) >> 1) as u8;
+    // Looks the RGB555 color up in a table built by a ColorCorrection
+    pub fn pixel_color(&self, palette: u8, color: u8, table: &[u32]) -> u32 {
+        let [r, g, b] = self.data[palette as usize][color as usize];
+        table[r as usize | (g as usize) << 5 | (b as usize) << 10]
+    }
 
-        (red, green, blue)
+    // Loads a 24-bit RGB color, keeping the top five bits of each channel
+    pub fn set_color(&mut self, palette: usize, color: usize, rgb: u32) {
+        self.data[palette][color] = [(rgb >> 19) as u8 & 0x1F, (rgb >> 11) as u8 & 0x1F, (rgb >> 3) as u8 & 0x1F];
     }
 
     pub fn write_spec_and_index(&mut self, value: u8) {
@@ -0,0 +1,363 @@
+use super::{
+    VIEWPORT_HEIGHT, VIEWPORT_WIDTH,
+    framebuffer::{FrameBuffer, xrgb},
+};
+
+pub const SGB_SCREEN_WIDTH: usize = 256;
+pub const SGB_SCREEN_HEIGHT: usize = 224;
+pub const TRANSFER_SIZE: usize = 0x1000;
+
+// Position of the Game Boy screen inside the border
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+const ATTRIBUTE_COLUMNS: usize = VIEWPORT_WIDTH / 8;
+const ATTRIBUTE_ROWS: usize = VIEWPORT_HEIGHT / 8;
+const SYSTEM_PALETTES: usize = 512;
+const ATTRIBUTE_FILES: usize = 45;
+const ATTRIBUTE_FILE_SIZE: usize = 90;
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_COLUMNS: usize = SGB_SCREEN_WIDTH / 8;
+const BORDER_ROWS: usize = SGB_SCREEN_HEIGHT / 8;
+const BORDER_PALETTES_OFFSET: usize = 0x800;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const ATTR_TRN: u8 = 0x15;
+const ATTR_SET: u8 = 0x16;
+const MASK_EN: u8 = 0x17;
+
+const DEFAULT_PALETTE: [u16; 4] = [0x7FFF, 0x5294, 0x294A, 0x0000];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Mask {
+    None,
+    Freeze,
+    Black,
+    Color0,
+}
+
+pub struct Sgb {
+    palettes: [[u16; 4]; 4],
+    system_palettes: Vec<[u16; 4]>,
+    attributes: [u8; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+    attribute_files: Vec<u8>,
+    border_tiles: Vec<u8>,
+    border_map: Vec<u16>,
+    border_palettes: [[u16; 16]; 4],
+    mask: Mask,
+    shades: Vec<u8>,
+    frozen_shades: Vec<u8>,
+    frame: FrameBuffer,
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Sgb {
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: vec![DEFAULT_PALETTE; SYSTEM_PALETTES],
+            attributes: [0; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+            attribute_files: vec![0; ATTRIBUTE_FILES * ATTRIBUTE_FILE_SIZE],
+            border_tiles: vec![0; 256 * BORDER_TILE_SIZE],
+            border_map: vec![0; BORDER_COLUMNS * BORDER_ROWS],
+            border_palettes: [[0; 16]; 4],
+            mask: Mask::None,
+            shades: vec![0; VIEWPORT_WIDTH * VIEWPORT_HEIGHT],
+            frozen_shades: vec![0; VIEWPORT_WIDTH * VIEWPORT_HEIGHT],
+            frame: FrameBuffer::new(SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT, false),
+        }
+    }
+
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+
+    pub fn set_shade(&mut self, offset: usize, shade: u8) {
+        self.shades[offset] = shade;
+    }
+
+    pub fn clear(&mut self) {
+        self.shades.fill(0);
+    }
+
+    // `transfer` is only called by the *_TRN commands and returns the 4KB shown on screen
+    pub fn command<F>(&mut self, data: &[u8], transfer: F)
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        // Every command is at least one full packet
+        let mut data = data.to_vec();
+        data.resize(data.len().max(16), 0);
+        match data[0] >> 3 {
+            PAL01 => self.set_palettes(0, 1, &data),
+            PAL23 => self.set_palettes(2, 3, &data),
+            PAL03 => self.set_palettes(0, 3, &data),
+            PAL12 => self.set_palettes(1, 2, &data),
+            ATTR_BLK => self.attribute_blocks(&data),
+            ATTR_LIN => self.attribute_lines(&data),
+            ATTR_DIV => self.attribute_divide(&data),
+            ATTR_CHR => self.attribute_characters(&data),
+            PAL_SET => self.palette_set(&data),
+            PAL_TRN => self.palette_transfer(&transfer()),
+            CHR_TRN => self.character_transfer(data[1] & 0x01 != 0, &transfer()),
+            PCT_TRN => self.picture_transfer(&transfer()),
+            ATTR_TRN => self.attribute_transfer(&transfer()),
+            ATTR_SET => self.attribute_set(data[1]),
+            MASK_EN => self.set_mask(data[1]),
+            // Sound, SNES program upload and the other commands have no visible effect
+            _ => {}
+        }
+    }
+
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color = |index: usize| u16::from_le_bytes([data[1 + index * 2], data[2 + index * 2]]);
+        // Color 0 is shared by all palettes
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for index in 1..4 {
+            self.palettes[first][index] = color(index);
+            self.palettes[second][index] = color(index + 3);
+        }
+    }
+
+    fn attribute_blocks(&mut self, data: &[u8]) {
+        let count = (data[1] as usize).min(18);
+        for block in data[2..].chunks_exact(6).take(count) {
+            let control = block[0] & 0x07;
+            let inside = block[1] & 0x03;
+            let outside = (block[1] >> 4) & 0x03;
+            // Changing only the inside or the outside also changes the surrounding line
+            let line = match control {
+                0x01 => Some(inside),
+                0x04 => Some(outside),
+                _ if control & 0x02 != 0 => Some((block[1] >> 2) & 0x03),
+                _ => None,
+            };
+            let (x1, y1, x2, y2) = (block[2] & 0x1F, block[3] & 0x1F, block[4] & 0x1F, block[5] & 0x1F);
+
+            for y in 0..ATTRIBUTE_ROWS as u8 {
+                for x in 0..ATTRIBUTE_COLUMNS as u8 {
+                    let palette = if x > x1 && x < x2 && y > y1 && y < y2 {
+                        (control & 0x01 != 0).then_some(inside)
+                    } else if x >= x1 && x <= x2 && y >= y1 && y <= y2 {
+                        line
+                    } else {
+                        (control & 0x04 != 0).then_some(outside)
+                    };
+                    if let Some(palette) = palette {
+                        self.attributes[y as usize * ATTRIBUTE_COLUMNS + x as usize] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    fn attribute_lines(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+        for line in data[2..].iter().take(count) {
+            let position = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0x03;
+            if line & 0x80 == 0 {
+                for y in 0..ATTRIBUTE_ROWS {
+                    if position < ATTRIBUTE_COLUMNS {
+                        self.attributes[y * ATTRIBUTE_COLUMNS + position] = palette;
+                    }
+                }
+            } else if position < ATTRIBUTE_ROWS {
+                self.attributes[position * ATTRIBUTE_COLUMNS..(position + 1) * ATTRIBUTE_COLUMNS].fill(palette);
+            }
+        }
+    }
+
+    fn attribute_divide(&mut self, data: &[u8]) {
+        let after = data[1] & 0x03;
+        let before = (data[1] >> 2) & 0x03;
+        let on_line = (data[1] >> 4) & 0x03;
+        let horizontal = data[1] & 0x40 != 0;
+        let position = (data[2] & 0x1F) as usize;
+
+        for y in 0..ATTRIBUTE_ROWS {
+            for x in 0..ATTRIBUTE_COLUMNS {
+                let coordinate = if horizontal { y } else { x };
+                self.attributes[y * ATTRIBUTE_COLUMNS + x] = match coordinate.cmp(&position) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    fn attribute_characters(&mut self, data: &[u8]) {
+        let mut x = (data[1] as usize).min(ATTRIBUTE_COLUMNS - 1);
+        let mut y = (data[2] as usize).min(ATTRIBUTE_ROWS - 1);
+        let count = (u16::from_le_bytes([data[3], data[4]]) as usize).min(ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS);
+        let vertical = data[5] & 0x01 != 0;
+
+        for index in 0..count {
+            let Some(byte) = data.get(6 + index / 4) else {
+                break;
+            };
+            self.attributes[y * ATTRIBUTE_COLUMNS + x] = (byte >> (6 - (index % 4) * 2)) & 0x03;
+
+            if vertical {
+                y += 1;
+                if y == ATTRIBUTE_ROWS {
+                    y = 0;
+                    x = (x + 1) % ATTRIBUTE_COLUMNS;
+                }
+            } else {
+                x += 1;
+                if x == ATTRIBUTE_COLUMNS {
+                    x = 0;
+                    y = (y + 1) % ATTRIBUTE_ROWS;
+                }
+            }
+        }
+    }
+
+    fn palette_set(&mut self, data: &[u8]) {
+        for palette in 0..4 {
+            let index = u16::from_le_bytes([data[1 + palette * 2], data[2 + palette * 2]]) as usize % SYSTEM_PALETTES;
+            self.palettes[palette] = self.system_palettes[index];
+        }
+        if data[9] & 0x80 != 0 {
+            self.apply_attribute_file(data[9] & 0x3F);
+        }
+        if data[9] & 0x40 != 0 {
+            self.set_mask(0);
+        }
+    }
+
+    fn palette_transfer(&mut self, transfer: &[u8]) {
+        for (palette, colors) in self.system_palettes.iter_mut().zip(transfer.chunks_exact(8)) {
+            for (color, bytes) in palette.iter_mut().zip(colors.chunks_exact(2)) {
+                *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+            }
+        }
+    }
+
+    fn character_transfer(&mut self, upper: bool, transfer: &[u8]) {
+        let offset = if upper { TRANSFER_SIZE } else { 0 };
+        let length = transfer.len().min(TRANSFER_SIZE);
+        self.border_tiles[offset..offset + length].copy_from_slice(&transfer[..length]);
+    }
+
+    fn picture_transfer(&mut self, transfer: &[u8]) {
+        for (entry, bytes) in self.border_map.iter_mut().zip(transfer.chunks_exact(2)) {
+            *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        let palettes = transfer.get(BORDER_PALETTES_OFFSET..).unwrap_or_default();
+        for (palette, colors) in self.border_palettes.iter_mut().zip(palettes.chunks_exact(32)) {
+            for (color, bytes) in palette.iter_mut().zip(colors.chunks_exact(2)) {
+                *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+            }
+        }
+    }
+
+    fn attribute_transfer(&mut self, transfer: &[u8]) {
+        let length = transfer.len().min(self.attribute_files.len());
+        self.attribute_files[..length].copy_from_slice(&transfer[..length]);
+    }
+
+    fn attribute_set(&mut self, value: u8) {
+        self.apply_attribute_file(value & 0x3F);
+        if value & 0x40 != 0 {
+            self.set_mask(0);
+        }
+    }
+
+    fn apply_attribute_file(&mut self, file: u8) {
+        let file = file as usize;
+        if file >= ATTRIBUTE_FILES {
+            return;
+        }
+        let data = &self.attribute_files[file * ATTRIBUTE_FILE_SIZE..(file + 1) * ATTRIBUTE_FILE_SIZE];
+        for (index, attribute) in self.attributes.iter_mut().enumerate() {
+            *attribute = (data[index / 4] >> (6 - (index % 4) * 2)) & 0x03;
+        }
+    }
+
+    fn set_mask(&mut self, value: u8) {
+        self.mask = match value & 0x03 {
+            0 => Mask::None,
+            1 => Mask::Freeze,
+            2 => Mask::Black,
+            _ => Mask::Color0,
+        };
+        if self.mask == Mask::Freeze {
+            self.frozen_shades.copy_from_slice(&self.shades);
+        }
+    }
+
+    // Composites the colorized Game Boy screen into the border
+    pub fn render_frame(&mut self) {
+        let backdrop = self.palettes[0][0];
+        for y in 0..SGB_SCREEN_HEIGHT {
+            for x in 0..SGB_SCREEN_WIDTH {
+                let color = self.border_color(x, y).unwrap_or(backdrop);
+                self.frame.set(y * SGB_SCREEN_WIDTH + x, rgb(color), 0);
+            }
+        }
+
+        let shades = match self.mask {
+            Mask::Freeze => &self.frozen_shades,
+            _ => &self.shades,
+        };
+        for y in 0..VIEWPORT_HEIGHT {
+            for x in 0..VIEWPORT_WIDTH {
+                let palette = self.attributes[(y / 8) * ATTRIBUTE_COLUMNS + x / 8] as usize;
+                let color = match (self.mask, shades[y * VIEWPORT_WIDTH + x]) {
+                    (Mask::Black, _) => 0x0000,
+                    (Mask::Color0, _) | (_, 0) => backdrop,
+                    (_, shade) => self.palettes[palette][shade as usize],
+                };
+                self.frame.set((SCREEN_Y + y) * SGB_SCREEN_WIDTH + SCREEN_X + x, rgb(color), 0);
+            }
+        }
+    }
+
+    // SNES 4bpp tile pixel, color 0 is transparent
+    fn border_color(&self, x: usize, y: usize) -> Option<u16> {
+        let entry = self.border_map[(y / 8) * BORDER_COLUMNS + x / 8];
+        let tile = (entry & 0xFF) as usize;
+        let palette = ((entry >> 10) & 0x03) as usize;
+        let pixel_x = if entry & 0x4000 != 0 { 7 - x % 8 } else { x % 8 };
+        let pixel_y = if entry & 0x8000 != 0 { 7 - y % 8 } else { y % 8 };
+
+        let row = tile * BORDER_TILE_SIZE + pixel_y * 2;
+        let bit = 7 - pixel_x;
+        let planes = [
+            self.border_tiles[row],
+            self.border_tiles[row + 1],
+            self.border_tiles[row + 16],
+            self.border_tiles[row + 17],
+        ];
+        let index = planes
+            .iter()
+            .enumerate()
+            .fold(0, |index, (plane, byte)| index | (((byte >> bit) & 0x01) << plane));
+        match index {
+            0 => None,
+            _ => Some(self.border_palettes[palette][index as usize]),
+        }
+    }
+}
+
+fn rgb(color: u16) -> u32 {
+    let expand = |value: u16| {
+        let value = (value & 0x1F) as u8;
+        (value << 3) | (value >> 2)
+    };
+    xrgb(expand(color), expand(color >> 5), expand(color >> 10))
+}
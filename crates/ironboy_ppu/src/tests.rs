@@ -0,0 +1,268 @@
+use ironboy_common::{GameBoyMode, OamAccess, SystemMemoryAccess};
+
+use crate::{
+    ColorCorrection, Ppu,
+    compatibility::{CompatibilityPalette, DEFAULT_PALETTE},
+    dmg_palette::{DmgPalette, DmgPalettePreset, PaletteError},
+    frame_blending::FrameBlending,
+    framebuffer::FrameBuffer,
+    sgb::Sgb,
+};
+
+const RED: [u32; 4] = [0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000];
+const GREEN: [u32; 4] = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+const BLUE: [u32; 4] = [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000];
+
+fn title_palette(title: &str) -> CompatibilityPalette {
+    let mut raw_title = [0; 16];
+    raw_title[..title.len()].copy_from_slice(title.as_bytes());
+    CompatibilityPalette::for_title(&raw_title, true)
+}
+
+#[test]
+fn titles_pick_the_boot_rom_palette() {
+    assert_eq!(
+        title_palette("POKEMON RED"),
+        CompatibilityPalette {
+            bg: RED,
+            obj0: GREEN,
+            obj1: RED
+        }
+    );
+    assert_eq!(
+        title_palette("POKEMON BLUE"),
+        CompatibilityPalette {
+            bg: BLUE,
+            obj0: RED,
+            obj1: BLUE
+        }
+    );
+    assert_eq!(
+        title_palette("ZELDA"),
+        CompatibilityPalette {
+            bg: RED,
+            obj0: [0xFFFFFF, 0x00FF00, 0x318400, 0x004A00],
+            obj1: BLUE
+        }
+    );
+    assert_eq!(
+        title_palette("MARIOLAND2"),
+        CompatibilityPalette {
+            bg: [0xFFFFCE, 0x63EFEF, 0x9C8431, 0x5A5A5A],
+            obj0: [0xFFFFFF, 0xFF7300, 0x944200, 0x000000],
+            obj1: BLUE
+        }
+    );
+    let tetris = [0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000];
+    assert_eq!(
+        title_palette("TETRIS"),
+        CompatibilityPalette {
+            bg: tetris,
+            obj0: tetris,
+            obj1: tetris
+        }
+    );
+}
+
+#[test]
+fn shared_checksums_use_the_fourth_letter() {
+    let super_mario_land = title_palette("SUPER MARIOLAND");
+    assert_eq!(super_mario_land.bg, [0xFFFFFF, 0xADAD84, 0x42737B, 0x000000]);
+    assert_eq!(super_mario_land.obj1, [0xFFFFFF, 0x5ABDFF, 0xFF0000, 0x0000FF]);
+    // Same checksum as SUPER MARIOLAND with another fourth letter
+    assert_eq!(title_palette("SUPX? MARIOLAND"), DEFAULT_PALETTE);
+}
+
+#[test]
+fn unknown_and_unlicensed_titles_get_the_default_palette() {
+    assert_eq!(title_palette("HOMEBREW GAME"), DEFAULT_PALETTE);
+    let mut title = [0; 16];
+    title[..11].copy_from_slice(b"POKEMON RED");
+    assert_eq!(CompatibilityPalette::for_title(&title, false), DEFAULT_PALETTE);
+    assert_eq!(title_palette(""), DEFAULT_PALETTE);
+}
+
+fn run_frame(ppu: &mut Ppu) {
+    for _ in 0..70224 / 4 {
+        ppu.cycle(4);
+    }
+}
+
+#[test]
+fn disabled_background_is_drawn_white() {
+    let mut ppu = Ppu::new(GameBoyMode::Monochrome);
+    ppu.set_index_buffer(true);
+    ppu.write_8(0xFF40, 0x00);
+    // Tile 0, which fills the whole BG map, is color 3 everywhere
+    for address in 0x8000..0x8010 {
+        ppu.write_8(address, 0xFF);
+    }
+    // Inverted palette, color 3 is shown with the lightest shade
+    ppu.write_8(0xFF47, 0x1B);
+    ppu.write_8(0xFF40, 0x91);
+    run_frame(&mut ppu);
+    run_frame(&mut ppu);
+    let white = DmgPalettePreset::Gray.palette().bg[0];
+    assert!(ppu.frame().pixels().iter().all(|&color| color == white));
+    assert!(ppu.frame().indices().unwrap().iter().all(|&index| index == 3));
+
+    ppu.write_8(0xFF47, 0xE4);
+    run_frame(&mut ppu);
+    ppu.write_8(0xFF40, 0x90);
+    for _ in 0..3 {
+        run_frame(&mut ppu);
+        assert!(ppu.frame().pixels().iter().all(|&color| color == white));
+        assert!(ppu.frame().indices().unwrap().iter().all(|&index| index == 0));
+    }
+}
+
+#[test]
+fn unknown_addresses_are_ignored() {
+    let mut ppu = Ppu::new(GameBoyMode::Color);
+    let registers: Vec<u8> = (0xFF40..=0xFF6B).map(|address| ppu.read_8(address)).collect();
+    for address in [0x0000, 0x7FFF, 0xA000, 0xFEA0, 0xFEFF, 0xFF46, 0xFF50, 0xFF67, 0xFF6C, 0xFFFF] {
+        ppu.write_8(address, 0x5A);
+        assert_eq!(ppu.read_8(address), if address == 0xFF46 { 0 } else { 0xFF });
+    }
+    assert_eq!((0xFF40..=0xFF6B).map(|address| ppu.read_8(address)).collect::<Vec<u8>>(), registers);
+}
+
+// Color of a Game Boy screen pixel once placed inside the SGB border
+fn sgb_pixel(sgb: &Sgb, x: usize, y: usize) -> u32 {
+    sgb.frame().pixels()[(40 + y) * 256 + 48 + x]
+}
+
+#[test]
+fn sgb_attribute_block_picks_the_palettes() {
+    let mut sgb = Sgb::new();
+    // PAL01: shared color 0, then colors 1-3 of palettes 0 and 1
+    let colors: [u16; 7] = [0x7FFF, 0x001F, 0x03E0, 0x7C00, 0x7FE0, 0x03FF, 0x0000];
+    let mut pal01 = vec![0x01];
+    pal01.extend(colors.iter().flat_map(|color| color.to_le_bytes()));
+    sgb.command(&pal01, Vec::new);
+    // ATTR_BLK: one block from tile (1, 1) to (4, 4) changing only its inside to palette 1
+    sgb.command(&[0x21, 0x01, 0x01, 0x01, 1, 1, 4, 4], Vec::new);
+
+    for offset in 0..160 * 144 {
+        sgb.set_shade(offset, 1);
+    }
+    sgb.set_shade(0, 0);
+    sgb.render_frame();
+    assert_eq!(sgb_pixel(&sgb, 0, 0), 0xFFFFFF);
+    assert_eq!(sgb_pixel(&sgb, 1, 0), 0xFF0000);
+    assert_eq!(sgb_pixel(&sgb, 47, 47), 0xFF0000);
+    // The inside and, since only the inside changes, the block's outline
+    assert_eq!(sgb_pixel(&sgb, 8, 8), 0x00FFFF);
+    assert_eq!(sgb_pixel(&sgb, 20, 20), 0x00FFFF);
+    assert_eq!(sgb_pixel(&sgb, 39, 39), 0x00FFFF);
+    assert_eq!(sgb_pixel(&sgb, 40, 40), 0xFF0000);
+}
+
+fn oam_row(ppu: &Ppu, row: u16) -> Vec<u8> {
+    (row * 8..row * 8 + 8).map(|address| ppu.read_oam(address)).collect()
+}
+
+#[test]
+fn oam_bug_mixes_the_scanned_row_with_the_previous_one() {
+    let previous = [0xFF, 0x00, 0x01, 0x02, 0x33, 0x33, 0x05, 0x06];
+    for access in [OamAccess::Write, OamAccess::Read] {
+        let mut ppu = Ppu::new(GameBoyMode::Monochrome);
+        ppu.write_8(0xFF40, 0x00);
+        for (offset, value) in previous.into_iter().chain([0x0F, 0x0F, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26]).enumerate() {
+            ppu.write_8(0xFE08 + offset as u16, value);
+        }
+        // Nothing happens with the LCD off
+        ppu.corrupt_oam(access);
+        assert_eq!(oam_row(&ppu, 2)[..2], [0x0F, 0x0F]);
+
+        // Row 2 is scanned 8 cycles into the line
+        ppu.write_8(0xFF40, 0x91);
+        ppu.cycle(8);
+        ppu.corrupt_oam(access);
+        let first = match access {
+            OamAccess::Write => [0x3F, 0x03],
+            OamAccess::Read => [0xFF, 0x03],
+        };
+        assert_eq!(oam_row(&ppu, 2), [&first[..], &previous[2..]].concat());
+        assert_eq!(oam_row(&ppu, 1), previous);
+    }
+}
+
+#[test]
+fn dmg_palette_files_are_parsed() {
+    let text = "# Autumn\nbg = #FFF6D3, #F9A875, #EB6B6F, #7C3F58\n\nOBJ0: 0xFFFFFF 0xAAAAAA 0x555555 0x000000\n";
+    let palette = DmgPalette::parse(text).unwrap();
+    assert_eq!(palette.bg, [0xFFF6D3, 0xF9A875, 0xEB6B6F, 0x7C3F58]);
+    assert_eq!(palette.obj0, [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]);
+    // Layers left out fall back to the background colors
+    assert_eq!(palette.obj1, palette.bg);
+    assert_eq!(
+        DmgPalette::parse("bg\t9BBC0F 8BAC0F 306230 0F380F"),
+        Ok(DmgPalettePreset::Green.palette())
+    );
+}
+
+#[test]
+fn dmg_palette_files_with_bad_lines_are_rejected() {
+    let bg = "bg = FFFFFF C0C0C0 606060 000000\n";
+    // Three and five colors
+    assert_eq!(
+        DmgPalette::parse(&format!("{bg}obj0 = FFFFFF C0C0C0 606060")),
+        Err(PaletteError::InvalidLine(2))
+    );
+    assert_eq!(
+        DmgPalette::parse(&format!("{bg}obj0 = FFFFFF C0C0C0 606060 000000 000000")),
+        Err(PaletteError::InvalidLine(2))
+    );
+    // Not hex, and not six digits
+    assert_eq!(DmgPalette::parse("bg = FFFFFF C0C0C0 606060 00000G"), Err(PaletteError::InvalidLine(1)));
+    assert_eq!(DmgPalette::parse("bg = FFF C0C0C0 606060 000000"), Err(PaletteError::InvalidLine(1)));
+    assert_eq!(DmgPalette::parse("bg"), Err(PaletteError::InvalidLine(1)));
+
+    assert_eq!(
+        DmgPalette::parse(&format!("{bg}obj2 = FFFFFF C0C0C0 606060 000000")),
+        Err(PaletteError::UnknownLayer("obj2".to_string()))
+    );
+    assert_eq!(DmgPalette::parse("# Empty\n"), Err(PaletteError::MissingBackground));
+}
+
+#[test]
+fn raw_colors_scale_each_channel() {
+    assert_eq!(ColorCorrection::Raw.convert(0x1F, 0x00, 0x10), (0xFF, 0x00, 0x84));
+    let table = ColorCorrection::Raw.table();
+    assert_eq!(table[0x7FFF], 0xFFFFFF);
+    assert_eq!(table[0x001F], 0xFF0000);
+}
+
+#[test]
+fn gbc_lcd_colors_mix_the_channels() {
+    for (color, expected) in [
+        ((31, 31, 31), (248, 248, 248)),
+        ((31, 0, 0), (201, 0, 46)),
+        ((0, 31, 0), (31, 186, 31)),
+        ((0, 0, 31), (15, 62, 170)),
+        ((10, 20, 5), (87, 130, 62)),
+    ] {
+        assert_eq!(ColorCorrection::GbcLcd.convert(color.0, color.1, color.2), expected);
+    }
+}
+
+#[test]
+fn flickering_pixels_blend_by_persistence() {
+    let persistence = 0.6;
+    let mut blending = FrameBlending::new(persistence, 2, 1);
+    let mut screen = FrameBuffer::new(2, 1, false);
+    // The first pixel flickers between white and black, the second stays gray
+    for frame in 0..60 {
+        let shade = if frame % 2 == 0 { 0xFFFFFF } else { 0x000000 };
+        screen.pixels_mut().copy_from_slice(&[shade, 0x808080]);
+        blending.blend(&screen);
+    }
+
+    // Each frame is mixed with the one before it, which settles at (new + p * old) / (1 + p)
+    let settled = |new: f32, old: f32| ((new + persistence * old) / (1.0 + persistence)).round() as u32 * 0x010101;
+    assert_eq!(blending.frame().pixels(), [settled(0.0, 255.0), 0x808080]);
+    screen.pixels_mut().copy_from_slice(&[0xFFFFFF, 0x808080]);
+    blending.blend(&screen);
+    assert_eq!(blending.frame().pixels(), [settled(255.0, 0.0), 0x808080]);
+}
@@ -1,5 +1,8 @@
 use ironboy_common::SystemMemoryAccess;
 
+#[cfg(test)]
+mod tests;
+
 pub struct SerialTransfer {
     data: u8,
     message: String,
@@ -23,7 +26,7 @@ impl SystemMemoryAccess for SerialTransfer {
         match address {
             0xFF01 => self.data,
             0xFF02 => self.control,
-            _ => panic!("Serial Transfer does not handle read to address {:4X}", address),
+            _ => 0xFF,
         }
     }
 
@@ -40,7 +43,7 @@ impl SystemMemoryAccess for SerialTransfer {
                     println!("{}", self.message);
                 }
             }
-            _ => panic!("Serial Transfer does not handle write to address {:4X}", address),
+            _ => {}
         }
     }
 }
@@ -0,0 +1,16 @@
+use ironboy_common::SystemMemoryAccess;
+
+use crate::SerialTransfer;
+
+#[test]
+fn unexpected_addresses_read_open_bus_and_ignore_writes() {
+    let mut serial = SerialTransfer::new();
+    serial.write_8(0xFF01, 0x42);
+    for address in [0x0000, 0xFF00, 0xFF03, 0xFFFF] {
+        serial.write_8(address, 0x81);
+        assert_eq!(serial.read_8(address), 0xFF);
+    }
+    assert_eq!(serial.read_8(0xFF01), 0x42);
+    assert_eq!(serial.read_8(0xFF02), 0x00);
+    assert_eq!(serial.interrupt, 0);
+}
@@ -1,6 +1,6 @@
 use ironboy_apu::Apu;
-use ironboy_cartridge::Cartridge;
-use ironboy_common::{GameBoyMode, MemoryInterface, SystemMemoryAccess};
+use ironboy_cartridge::{CameraImage, Cartridge, RomInfo};
+use ironboy_common::{GameBoyMode, MemoryInterface, Model, OamAccess, SystemMemoryAccess};
 use ironboy_joypad::JoyPad;
 use ironboy_ppu::Ppu;
 use ironboy_serial_transfer::SerialTransfer;
@@ -19,8 +19,10 @@ enum TransferMode {
 pub struct SystemBus {
     cartridge: Cartridge,
     game_boy_mode: GameBoyMode,
+    model: Model,
     double_speed: bool,
     speed_switch_armed: bool,
+    stopped: bool,
     wram_bank: usize,
     wram: [u8; WRAM_SIZE],
     hram: [u8; HRAM_SIZE],
@@ -31,6 +33,8 @@ pub struct SystemBus {
     interrupt_enable: u8,
     interrupt_flag: u8,
     undocumented_cgb_registers: [u8; 3],
+    // Cycles of the 4 MHz clock since power-on, double speed doesn't make it run faster
+    elapsed_cycles: u64,
     pub joy_pad: JoyPad,
     pub serial_transfer: SerialTransfer,
     pub timer: Timer,
@@ -56,7 +60,8 @@ impl SystemMemoryAccess for SystemBus {
             0xFF4D | 0xFF4F | 0xFF51..=0xFF56 | 0xFF70 | 0xFF72..=0xFF77 if self.game_boy_mode != GameBoyMode::Color => 0xFF,
             0xFF4D => ((self.double_speed as u8) << 7) | 0x7E | (self.speed_switch_armed as u8),
             0xFF4F => self.ppu.read_8(address),
-            0xFF50 => todo!("Set to non-zero to disable boot ROM"),
+            // Boot ROMs are not emulated, the register reads as already unmapped
+            0xFF50 => 0xFF,
             0xFF51..=0xFF55 => self.read_hdma(address),
             0xFF56 => 0xFF, //todo!("Infrared Comms"),
             0xFF68..=0xFF6C => self.ppu.read_8(address),
@@ -78,7 +83,12 @@ impl SystemMemoryAccess for SystemBus {
             0xC000..=0xCFFF | 0xE000..=0xEFFF => self.wram[address as usize & 0x0FFF] = value,
             0xD000..=0xDFFF | 0xF000..=0xFDFF => self.wram[(self.wram_bank * 0x1000) | address as usize & 0x0FFF] = value,
             0xFE00..=0xFE9F => self.ppu.write_8(address, value),
-            0xFF00 => self.joy_pad.write_8(address, value),
+            0xFF00 => {
+                self.joy_pad.write_8(address, value);
+                if let Some(command) = self.joy_pad.take_sgb_command() {
+                    self.ppu.sgb_command(&command);
+                }
+            }
             0xFF01..=0xFF02 => self.serial_transfer.write_8(address, value),
             0xFF04..=0xFF07 => self.timer.write_8(address, value),
             0xFF0F => self.interrupt_flag = value,
@@ -119,10 +129,19 @@ impl MemoryInterface for SystemBus {
     }
 
     fn cycle(&mut self, cycles: u32, cpu_halted: bool) -> u32 {
+        // Only the joypad can wake the system from STOP mode, every other clock is halted
         let speed = if self.double_speed { 2 } else { 1 };
+        if self.stopped {
+            self.elapsed_cycles += (cycles / speed) as u64;
+            self.interrupt_flag |= self.joy_pad.interrupt;
+            self.joy_pad.interrupt = 0;
+            return cycles;
+        }
+
         let vram_cycles = self.vram_dma_cycle(cpu_halted);
         let cpu_cycles = cycles + vram_cycles * speed;
         let ppu_cycles = cycles / speed + vram_cycles;
+        self.elapsed_cycles += ppu_cycles as u64;
 
         self.timer.cycle(cpu_cycles);
         self.interrupt_flag |= self.timer.interrupt;
@@ -134,6 +153,8 @@ impl MemoryInterface for SystemBus {
 
         self.apu.cycle(ppu_cycles);
 
+        self.cartridge.mbc.cycle(ppu_cycles);
+
         self.interrupt_flag |= self.joy_pad.interrupt;
         self.joy_pad.interrupt = 0;
 
@@ -143,22 +164,43 @@ impl MemoryInterface for SystemBus {
         cycles
     }
 
-    fn change_speed(&mut self) {
-        if self.speed_switch_armed {
+    fn change_speed(&mut self) -> bool {
+        let armed = self.speed_switch_armed;
+        if armed {
             self.double_speed = !self.double_speed;
+            self.timer.reset_divider();
         }
         self.speed_switch_armed = false;
+        armed
+    }
+
+    fn set_stopped(&mut self, stopped: bool) {
+        if stopped {
+            self.timer.reset_divider();
+        }
+        self.stopped = stopped;
+    }
+
+    fn joypad_active(&self) -> bool {
+        self.joy_pad.any_pressed()
+    }
+
+    fn corrupt_oam(&mut self, address: u16, access: OamAccess) {
+        if !self.model.is_color() && (0xFE00..=0xFEFF).contains(&address) {
+            self.ppu.corrupt_oam(access);
+        }
     }
 }
 
 impl SystemBus {
-    pub fn new(cartridge: Cartridge) -> Self {
-        let mode = cartridge.mode();
+    pub fn new(cartridge: Cartridge, mode: GameBoyMode, model: Model) -> Self {
         let mut bus = SystemBus {
             cartridge,
             game_boy_mode: mode,
+            model,
             double_speed: false,
             speed_switch_armed: false,
+            stopped: false,
             wram_bank: 1,
             wram: [0; WRAM_SIZE],
             hram: [0; HRAM_SIZE],
@@ -169,13 +211,17 @@ impl SystemBus {
             interrupt_enable: 0,
             interrupt_flag: 0,
             undocumented_cgb_registers: [0; 3],
+            elapsed_cycles: 0,
             joy_pad: JoyPad::new(),
             serial_transfer: SerialTransfer::new(),
             timer: Timer::new(),
             ppu: Ppu::new(mode),
-            apu: Apu::new(),
+            apu: Apu::new(model),
         };
 
+        if mode == GameBoyMode::SuperGameBoy {
+            bus.joy_pad.enable_sgb();
+        }
         bus.set_hardware_registers();
         bus
     }
@@ -211,6 +257,30 @@ impl SystemBus {
         self.store_8(0xFF49, 0xFF);
         self.store_8(0xFF4A, 0);
         self.store_8(0xFF4B, 0);
+        // The monochrome boot ROMs always take the same time, on other models it depends on the logo
+        if matches!(self.model, Model::Dmg | Model::Mgb) {
+            self.timer.set_divider(0xABCC);
+        }
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cartridge.mbc.set_tilt(x, y);
+    }
+
+    pub fn rom_info(&self) -> &RomInfo {
+        self.cartridge.info()
+    }
+
+    pub fn elapsed_cycles(&self) -> u64 {
+        self.elapsed_cycles
+    }
+
+    pub fn rumble_active(&self) -> bool {
+        self.cartridge.mbc.rumble_active()
+    }
+
+    pub fn set_camera_image(&mut self, image: CameraImage) {
+        self.cartridge.mbc.set_camera_image(image);
     }
 
     pub fn oam_dma(&mut self, value: u8) {
@@ -225,7 +295,7 @@ impl SystemBus {
         match address {
             0xFF51..=0xFF54 => 0xFF,
             0xFF55 => ((self.hdma_mode == TransferMode::Stopped) as u8) << 7 | self.hdma_length,
-            _ => panic!("HDMA does not handle read {:04X}", address),
+            _ => 0xFF,
         }
     }
 
@@ -248,9 +318,10 @@ impl SystemBus {
                     };
                     self.hdma_length = (value & 0x7F) + 1;
                 }
-                TransferMode::GeneralPurpose => panic!("Cannot cancel General Purpose DMA"),
+                // The CPU is stalled for the whole transfer so it can never be cancelled
+                TransferMode::GeneralPurpose => {}
             },
-            _ => panic!("HDMA does not handle write {:04X}", address),
+            _ => {}
         };
     }
 
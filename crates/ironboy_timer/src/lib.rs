@@ -1,5 +1,8 @@
 use ironboy_common::SystemMemoryAccess;
 
+#[cfg(test)]
+mod tests;
+
 pub struct Timer {
     divider: u8,
     internal_divider: u32,
@@ -27,7 +30,7 @@ impl SystemMemoryAccess for Timer {
                         _ => 0,
                     })
             }
-            _ => panic!("Timer does not handle read to address {:4X}", address),
+            _ => 0xFF,
         }
     }
 
@@ -45,7 +48,7 @@ impl SystemMemoryAccess for Timer {
                     _ => 1024,
                 };
             }
-            _ => panic!("Timer does not handle write to address {:4X}", address),
+            _ => {}
         }
     }
 }
@@ -64,6 +67,17 @@ impl Timer {
         }
     }
 
+    // Full 16-bit system counter, DIV is its upper byte
+    pub fn set_divider(&mut self, value: u16) {
+        self.divider = (value >> 8) as u8;
+        self.internal_divider = (value & 0xFF) as u32;
+    }
+
+    pub fn reset_divider(&mut self) {
+        self.divider = 0;
+        self.internal_divider = 0;
+    }
+
     pub fn cycle(&mut self, cycles: u32) {
         self.internal_divider += cycles;
         while self.internal_divider >= 256 {
@@ -0,0 +1,20 @@
+use ironboy_common::SystemMemoryAccess;
+
+use crate::Timer;
+
+fn registers(timer: &Timer) -> Vec<u8> {
+    (0xFF04..=0xFF07).map(|address| timer.read_8(address)).collect()
+}
+
+#[test]
+fn unexpected_addresses_read_open_bus_and_ignore_writes() {
+    let mut timer = Timer::new();
+    timer.write_8(0xFF06, 0x12);
+    timer.write_8(0xFF07, 0x05);
+    let before = registers(&timer);
+    for address in [0x0000, 0xFF03, 0xFF08, 0xFFFF] {
+        timer.write_8(address, 0x5A);
+        assert_eq!(timer.read_8(address), 0xFF);
+    }
+    assert_eq!(registers(&timer), before);
+}